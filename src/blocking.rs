@@ -0,0 +1,79 @@
+//! A blocking wrapper of the [`Engine`], for the simple modules not wanting to deal with `async`.
+//!
+//! Each call drives the underlying asynchronous [`Engine`] to completion on the current thread
+//! with [`futures::executor::block_on`], so it must not be used from within an asynchronous context.
+
+use std::io::{Stdin, Stdout};
+
+use futures::{AsyncRead, AsyncWrite, TryStreamExt, io::AllowStdIo};
+
+use crate::{
+    engine::{Engine, Request, Result},
+    wire::{MessageName, Params},
+};
+
+/// A blocking wrapper of an [`Engine`], see the [module-level](self) documentation.
+pub struct BlockingEngine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: Engine<I, O>,
+}
+
+impl BlockingEngine<AllowStdIo<Stdin>, AllowStdIo<Stdout>> {
+    /// Initialize a connection to the engine via standard I/O, see [`Engine::stdio`].
+    pub fn stdio() -> Self {
+        Self::new(Engine::stdio())
+    }
+}
+
+impl<I, O> BlockingEngine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// Wrap the `engine` to be driven with blocking calls.
+    pub fn new(engine: Engine<I, O>) -> Self {
+        Self { engine }
+    }
+
+    /// Unwrap the underlying [`Engine`].
+    pub fn into_inner(self) -> Engine<I, O> {
+        self.engine
+    }
+
+    /// See [`Engine::install`].
+    pub fn install(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<bool> {
+        futures::executor::block_on(self.engine.install(priority, name, filter))
+    }
+
+    /// See [`Engine::message`].
+    pub fn message(
+        &self,
+        name: impl Into<MessageName>,
+        retvalue: impl Into<String>,
+        kv: Params,
+    ) -> Result<(bool, String, Params)> {
+        futures::executor::block_on(self.engine.message(name, retvalue, kv))
+    }
+
+    /// Wait for the next message from the engine for processing,
+    /// or `None` if the engine stopped sending them.
+    ///
+    /// The messages received while not waiting here, such as while waiting for
+    /// an acknowledgement in [`Self::install`], are acknowledged as not processed.
+    pub fn next_message(&self) -> Result<Option<Request>> {
+        futures::executor::block_on(self.engine.messages().try_next())
+    }
+
+    /// See [`Engine::ack`].
+    pub fn ack(&self, req: Request, processed: bool) -> Result<()> {
+        futures::executor::block_on(self.engine.ack(req, processed))
+    }
+}
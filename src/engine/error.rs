@@ -6,6 +6,19 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 /// An error that may occur when interracting with the engine.
 #[derive(Debug, Error)]
 pub enum Error {
+    /// The `command` sent to the engine failed, see the `source` for the cause.
+    ///
+    /// The cause may be matched directly with [`Error::root`].
+    #[error("`{command}` command failed: {source}")]
+    Request {
+        /// The failed command, such as `install` or `message`.
+        command: &'static str,
+
+        /// The cause of the failure.
+        #[source]
+        source: Box<Error>,
+    },
+
     /// An I/O error occured.
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -17,4 +30,84 @@ pub enum Error {
     /// The data stream was closed before expected.
     #[error("got an unexpected end of stream from engine")]
     UnexpectedEof,
+
+    /// The engine didn't answer a probe, the link is considered dead.
+    #[error("the engine didn't answer the watchdog probe, link is dead")]
+    LinkDead,
+
+    /// The engine rejected the line sent for the request, as echoed in it's `Error in` notification.
+    #[error("the engine rejected the line: {0}")]
+    EngineRejected(String),
+
+    /// The line to send doesn't fit in the engine's line buffer, see [`Engine::with_bufsize`](super::Engine::with_bufsize).
+    #[error("line of {len} bytes doesn't fit the engine's line buffer of {max} bytes")]
+    LineTooLong {
+        /// Length of the line, without it's newline.
+        len: usize,
+
+        /// Size of the engine's line buffer.
+        max: usize,
+    },
+
+    /// The channel was provided for a connection role not attaching to a channel, or conversely.
+    #[error("invalid channel for the `{0:?}` connection role")]
+    InvalidConnectRole(crate::wire::ConnectRole),
+
+    /// The engine closed the connection right after the `connect` request, rejecting it.
+    #[error("the engine closed the connection, rejecting the connect request")]
+    ConnectRejected,
+
+    /// The engine didn't answer in the allotted time.
+    #[error("timed out waiting for the engine")]
+    Timeout,
+
+    /// The value of a typed _local variable_ was invalid.
+    #[error("invalid value `{value}` for local variable `{name}`")]
+    InvalidParamValue {
+        /// Name of the local variable.
+        name: String,
+
+        /// The invalid value.
+        value: String,
+    },
+
+    /// The message handler panicked while processing the message.
+    #[error("the handler panicked processing message `{id}`: {panic}")]
+    HandlerPanicked {
+        /// Identifier of the message being processed.
+        id: String,
+
+        /// The panic payload's message.
+        panic: String,
+    },
+}
+
+impl Error {
+    /// Whether the error is caused by the connection to the engine rather than by the exchanged data,
+    /// so that re-dialing the engine and retrying may succeed.
+    ///
+    /// A transport failure, a closed or dead link and a timeout are recoverable,
+    /// while a malformed or rejected line, an invalid value or a panicked handler
+    /// would fail the same way when retried, and are fatal.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::Request { source, .. } => source.is_recoverable(),
+            Self::Io(_) | Self::UnexpectedEof | Self::LinkDead | Self::Timeout => true,
+            Self::Format(_)
+            | Self::EngineRejected(_)
+            | Self::LineTooLong { .. }
+            | Self::ConnectRejected
+            | Self::InvalidConnectRole(_)
+            | Self::InvalidParamValue { .. }
+            | Self::HandlerPanicked { .. } => false,
+        }
+    }
+
+    /// The underlying cause of the error, stripped of the [`Error::Request`] context.
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Request { source, .. } => source.root(),
+            other => other,
+        }
+    }
 }
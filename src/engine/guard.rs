@@ -0,0 +1,118 @@
+use futures::{AsyncRead, AsyncWrite};
+
+use super::{Engine, Result};
+use crate::wire::{self, MessageName, UninstallAck};
+
+/// The uninstalls of the dropped [`HandlerGuard`]s, queued until they are written,
+/// and then awaiting their acknowledgement.
+#[derive(Default)]
+pub(super) struct Uninstalls {
+    pub queued: Vec<String>,
+    pub sent: Vec<String>,
+}
+
+/// A guard over a handler installed with [`Engine::install_guarded`],
+/// uninstalling it when dropped.
+///
+/// As dropping can't be asynchronous, the `uninstall` is queued and only written with
+/// the next line sent to the engine, or by [`Engine::flush_uninstalls`]; the handler stays in
+/// [`Engine::handlers`] until the engine acknowledges it's removal.
+/// Use [`HandlerGuard::release`] to uninstall the handler deterministically.
+#[must_use = "the handler is uninstalled when the guard is dropped"]
+pub struct HandlerGuard<'e, I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: &'e Engine<I, O>,
+    name: Option<String>,
+}
+
+impl<I, O> HandlerGuard<'_, I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// The name of the guarded handler.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().expect("guard was already released")
+    }
+
+    /// Uninstall the guarded handler, waiting for the engine to acknowledge.
+    pub async fn release(mut self) -> Result<bool> {
+        let name = self.name.take().expect("guard was already released");
+
+        self.engine.uninstall(name).await
+    }
+}
+
+impl<I, O> Drop for HandlerGuard<'_, I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    fn drop(&mut self) {
+        if let Some(name) = self.name.take() {
+            tracing::debug!("handler guard dropped, queuing the uninstall of `{name}`");
+
+            self.engine.uninstalls.lock().unwrap().queued.push(name);
+        }
+    }
+}
+
+impl<I, O> Engine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// Request the engine to install a message handler with the provided `priority`,
+    /// returning a [`HandlerGuard`] uninstalling it when dropped, or `None` if it wasn't installed.
+    pub async fn install_guarded(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<Option<HandlerGuard<'_, I, O>>> {
+        let name = String::from(name.into());
+
+        Ok(self
+            .install(priority, &name, filter)
+            .await?
+            .then_some(HandlerGuard {
+                engine: self,
+                name: Some(name),
+            }))
+    }
+
+    /// Write the uninstalls queued by the dropped [`HandlerGuard`]s right away,
+    /// rather than with the next line sent to the engine.
+    pub async fn flush_uninstalls(&self) -> Result<()> {
+        if self.uninstalls.lock().unwrap().queued.is_empty() {
+            return Ok(());
+        }
+
+        self.write_lines(&[]).await
+    }
+
+    /// Handle the acknowledgement of an uninstall sent for a dropped [`HandlerGuard`],
+    /// returning whether the `recvd` line was one.
+    pub(super) fn uninstalled(&self, recvd: &str) -> bool {
+        let Ok(ack) = wire::from_str::<UninstallAck>(recvd) else {
+            return false;
+        };
+
+        let mut uninstalls = self.uninstalls.lock().unwrap();
+        let Some(idx) = uninstalls.sent.iter().position(|name| *name == ack.name) else {
+            return false;
+        };
+        uninstalls.sent.remove(idx);
+
+        if ack.success {
+            self.handlers.lock().unwrap().remove(&ack.name);
+        } else {
+            tracing::warn!("the engine refused to uninstall `{}`", ack.name);
+        }
+
+        true
+    }
+}
@@ -0,0 +1,54 @@
+use std::{ops::Deref, sync::Arc};
+
+use futures::{AsyncRead, AsyncWrite};
+
+use super::Engine;
+
+/// A cheaply clonable handle to a shared [`Engine`], created with [`Engine::into_handle`].
+///
+/// Every clone shares the same connection and subscriptions,
+/// so calls can be issued concurrently from multiple tasks.
+pub struct EngineHandle<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: Arc<Engine<I, O>>,
+}
+
+impl<I, O> Clone for EngineHandle<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+        }
+    }
+}
+
+impl<I, O> Deref for EngineHandle<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    type Target = Engine<I, O>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.engine
+    }
+}
+
+impl<I, O> Engine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// Turn the engine into a cheaply clonable [`EngineHandle`].
+    pub fn into_handle(self) -> EngineHandle<I, O> {
+        EngineHandle {
+            engine: Arc::new(self),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+#[cfg(doc)]
+use super::Engine;
+
+/// A message handler, as installed with [`Engine::install`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Handler {
+    /// Priority in chain, use default (`100`) if `None`.
+    pub priority: Option<u64>,
+
+    /// Filter for the installed handler, see [`crate::wire::Install::filter`].
+    pub filter: Option<(String, Option<String>)>,
+}
+
+/// A filter for an installed handler, to be passed to [`Engine::install`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    var: String,
+    value: Option<String>,
+}
+
+impl Filter {
+    /// Only handle messages having the `var` parameter.
+    pub fn on(var: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            value: None,
+        }
+    }
+
+    /// Only handle messages having the `var` parameter set to `value`.
+    pub fn eq(var: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            value: Some(value.into()),
+        }
+    }
+}
+
+impl From<Filter> for Option<(String, Option<String>)> {
+    fn from(filter: Filter) -> Self {
+        Some((filter.var, filter.value))
+    }
+}
+
+/// A set of message [`Handler`]s, keyed by message name.
+pub type HandlerSet = BTreeMap<String, Handler>;
+
+/// A report of the operations performed by [`Engine::reconfigure`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Reconfiguration {
+    /// Names of the newly installed handlers.
+    pub installed: Vec<String>,
+
+    /// Names of the uninstalled handlers.
+    pub uninstalled: Vec<String>,
+
+    /// Names of the handlers re-installed with different parameters.
+    pub reinstalled: Vec<String>,
+
+    /// Names of the handlers for which the engine refused an operation.
+    pub failed: Vec<String>,
+}
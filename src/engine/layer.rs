@@ -0,0 +1,89 @@
+use std::fmt::{self, Write};
+
+use futures::{AsyncRead, AsyncWrite, StreamExt, channel::mpsc};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{Layer, layer::Context};
+
+use super::{EngineHandle, Result};
+
+/// A [`Layer`] forwarding the `tracing` events to the engine's log,
+/// created along with it's forwarder with [`EngineLayer::new`].
+///
+/// The events of level [`Level::INFO`] and more severe are sent with [`Engine::output`](super::Engine::output),
+/// while the more verbose ones are sent with [`Engine::debug`](super::Engine::debug) at the matching
+/// [`DebugLevel`](crate::wire::DebugLevel), to be filtered by the engine's debug level.
+///
+/// The events emitted by this crate are not forwarded, to avoid a feedback loop.
+pub struct EngineLayer {
+    tx: mpsc::UnboundedSender<(Level, String)>,
+}
+
+impl EngineLayer {
+    /// Create a layer forwarding the events to the engine behind the `handle`,
+    /// along with the forwarder future, which must be polled for the events to be sent.
+    ///
+    /// The forwarder completes once the layer is dropped, or errors if sending fails.
+    pub fn new<I, O>(handle: EngineHandle<I, O>) -> (Self, impl Future<Output = Result<()>>)
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+    {
+        let (tx, mut rx) = mpsc::unbounded::<(Level, String)>();
+
+        let forward = async move {
+            while let Some((level, text)) = rx.next().await {
+                if level <= Level::INFO {
+                    handle.output(text).await?;
+                } else {
+                    handle.debug(level.into(), text).await?;
+                }
+            }
+
+            Ok(())
+        };
+
+        (Self { tx }, forward)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EngineLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let target = metadata.target();
+        if target == "yengine" || target.starts_with("yengine::") {
+            return;
+        }
+
+        let mut visitor = Visitor::default();
+        event.record(&mut visitor);
+
+        // The forwarder may be gone, the event is then lost
+        let _ = self.tx.unbounded_send((*metadata.level(), visitor.text));
+    }
+}
+
+/// Format the `message` of an event followed by it's other fields as `key=value`.
+#[derive(Default)]
+struct Visitor {
+    text: String,
+}
+
+impl Visit for Visitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{value}"));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+
+        let _ = match field.name() {
+            "message" => write!(self.text, "{value:?}"),
+            name => write!(self.text, "{name}={value:?}"),
+        };
+    }
+}
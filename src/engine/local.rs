@@ -0,0 +1,153 @@
+use std::{str::FromStr, time::Duration};
+
+use futures::{AsyncRead, AsyncWrite};
+
+use super::{Engine, Error, Result};
+use crate::wire;
+
+/// Local parameters holding an integer value.
+const INT_PARAMS: &[&str] = &["timeout", "bufsize"];
+
+/// Local parameters holding a boolean value.
+const BOOL_PARAMS: &[&str] = &[
+    "disconnected",
+    "timebomb",
+    "setdata",
+    "reenter",
+    "selfwatch",
+    "restart",
+];
+
+/// Validate the `value` of known typed local parameters.
+pub fn validate(name: &str, value: &str) -> Result<()> {
+    let valid = if INT_PARAMS.contains(&name) {
+        value.parse::<u64>().is_ok()
+    } else if BOOL_PARAMS.contains(&name) {
+        wire::parse_bool(value).is_some()
+    } else {
+        true
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidParamValue {
+            name: name.into(),
+            value: value.into(),
+        })
+    }
+}
+
+/// Parse the `value` of the typed _local variable_ `name`.
+fn parse<T: FromStr>(name: &str, value: String) -> Result<T> {
+    match value.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(Error::InvalidParamValue {
+            name: name.into(),
+            value,
+        }),
+    }
+}
+
+/// The identity of the engine, from it's read-only _local variables_, see [`Engine::engine_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineInfo {
+    /// The version of the engine, `engine.version`.
+    pub version: String,
+
+    /// The identifier of the engine's current run, `engine.runid`.
+    pub runid: u64,
+
+    /// The name of the node running the engine, `engine.nodename`.
+    pub nodename: String,
+
+    /// Whether the engine runs as a client, `engine.clientmode`.
+    pub clientmode: bool,
+
+    /// The maximum count of worker threads of the engine, `engine.maxworkers`.
+    pub maxworkers: u32,
+}
+
+impl<I, O> Engine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// Set the `timeout` _local variable_, for answering to messages.
+    pub async fn set_timeout(&self, timeout: Duration) -> Result<bool> {
+        self.setlocal("timeout", timeout.as_millis().to_string())
+            .await
+    }
+
+    /// Get the `timeout` _local variable_, for answering to messages.
+    pub async fn get_timeout(&self) -> Result<Duration> {
+        let value = self.getlocal("timeout").await?;
+
+        match value.parse() {
+            Ok(ms) => Ok(Duration::from_millis(ms)),
+            Err(_) => Err(Error::InvalidParamValue {
+                name: "timeout".into(),
+                value,
+            }),
+        }
+    }
+
+    /// Set the `timebomb` _local variable_, to terminate this module instance if a timeout occured.
+    pub async fn set_timebomb(&self, enabled: bool) -> Result<bool> {
+        self.setlocal("timebomb", enabled.to_string()).await
+    }
+
+    /// Set the `trackparam` _local variable_, the message handler tracking name, which cannot be made empty.
+    pub async fn set_trackparam(&self, name: &str) -> Result<bool> {
+        self.setlocal("trackparam", name).await
+    }
+
+    /// Set the `disconnected` _local variable_, to enable or disable sending `chan.disconnected` messages.
+    pub async fn set_disconnected(&self, enabled: bool) -> Result<bool> {
+        self.setlocal("disconnected", enabled.to_string()).await
+    }
+
+    /// Set the `reenter` _local variable_, to allow this module to handle messages generated by itself.
+    pub async fn set_reenter(&self, enabled: bool) -> Result<bool> {
+        self.setlocal("reenter", enabled.to_string()).await
+    }
+
+    /// Set the `bufsize` _local variable_, the length of the engine's incoming line buffer.
+    pub async fn set_bufsize(&self, bufsize: usize) -> Result<bool> {
+        self.setlocal("bufsize", bufsize.to_string()).await
+    }
+
+    /// Get the identity of the engine, querying it's _local variables_ on the first call only,
+    /// as they don't change for the life of the engine.
+    pub async fn engine_info(&self) -> Result<EngineInfo> {
+        if let Some(info) = self.info.get() {
+            return Ok(info.clone());
+        }
+
+        let (version, runid, nodename, clientmode, maxworkers) = futures::try_join!(
+            self.getlocal("engine.version"),
+            self.getlocal("engine.runid"),
+            self.getlocal("engine.nodename"),
+            self.getlocal("engine.clientmode"),
+            self.getlocal("engine.maxworkers"),
+        )?;
+
+        let info = EngineInfo {
+            version,
+            runid: parse("engine.runid", runid)?,
+            nodename,
+            clientmode: match wire::parse_bool(&clientmode) {
+                Some(clientmode) => clientmode,
+                None => {
+                    return Err(Error::InvalidParamValue {
+                        name: "engine.clientmode".into(),
+                        value: clientmode,
+                    });
+                }
+            },
+            maxworkers: parse("engine.maxworkers", maxworkers)?,
+        };
+
+        Ok(self.info.get_or_init(|| info).clone())
+    }
+}
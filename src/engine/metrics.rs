@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A snapshot of the traffic counters of an [`Engine`](super::Engine), see [`Engine::metrics`](super::Engine::metrics).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EngineMetrics {
+    /// The count of messages sent to the engine for processing.
+    pub messages_sent: u64,
+
+    /// The count of messages received from the engine for processing.
+    pub messages_received: u64,
+
+    /// The count of acknowledgements of received messages sent to the engine.
+    pub acks_sent: u64,
+
+    /// The count of bytes received from the engine, line terminators included.
+    pub bytes_in: u64,
+
+    /// The count of bytes sent to the engine, line terminators included.
+    pub bytes_out: u64,
+
+    /// The count of lines received for a subscriber that failed to be decoded.
+    pub decode_errors: u64,
+}
+
+/// The live traffic counters, incremented as lines are exchanged.
+#[derive(Default)]
+pub struct Metrics {
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    acks_sent: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    decode_errors: AtomicU64,
+}
+
+fn incr(counter: &AtomicU64, count: usize) {
+    counter.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+impl Metrics {
+    /// Account for the `line` sent to the engine.
+    pub fn sent(&self, line: &str) {
+        incr(&self.bytes_out, line.len() + 1);
+
+        if line.starts_with("%%>message:") {
+            incr(&self.messages_sent, 1);
+        } else if line.starts_with("%%<message:") {
+            incr(&self.acks_sent, 1);
+        }
+    }
+
+    /// Account for the `line` received from the engine.
+    pub fn received(&self, line: &str) {
+        incr(&self.bytes_in, line.len() + 1);
+
+        if line.starts_with("%%>message:") {
+            incr(&self.messages_received, 1);
+        }
+    }
+
+    /// Account for a line that failed to be decoded.
+    pub fn decode_error(&self) {
+        incr(&self.decode_errors, 1);
+    }
+
+    /// Read the current values of the counters.
+    pub fn snapshot(&self) -> EngineMetrics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        EngineMetrics {
+            messages_sent: load(&self.messages_sent),
+            messages_received: load(&self.messages_received),
+            acks_sent: load(&self.acks_sent),
+            bytes_in: load(&self.bytes_in),
+            bytes_out: load(&self.bytes_out),
+            decode_errors: load(&self.decode_errors),
+        }
+    }
+}
@@ -1,25 +1,31 @@
 //! An abstraction of the telephony [`Engine`].
 
 use std::{
-    collections::BTreeMap,
-    io::{self, Stdin, Stdout},
-    time::SystemTime,
+    fs::{File, OpenOptions},
+    io::{self, IoSlice, Stdin, Stdout},
+    path::Path,
+    pin::pin,
+    sync::{
+        self, Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use facet::Facet;
 use futures::{
-    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, StreamExt, TryStream, TryStreamExt,
-    io::{AllowStdIo, BufReader, Lines},
+    AsyncRead, AsyncWrite, AsyncWriteExt, Stream, StreamExt, TryStream, TryStreamExt,
+    future::{self, Either},
+    io::AllowStdIo,
     lock::Mutex,
 };
-use subable::{Item, Subable};
 
 use super::wire::{
     self, Connect, ConnectRole, Debug, DebugLevel, ErrorIn, Install, InstallAck, Message,
-    MessageAck, Output, Quit, QuitAck, SetLocal, SetLocalAck, Uninstall, UninstallAck, Unwatch,
-    UnwatchAck, Watch, WatchAck,
+    MessageAck, MessageName, Output, Params, Quit, QuitAck, SetLocal, SetLocalAck, Unacknowledged,
+    Uninstall, UninstallAck, Unwatch, UnwatchAck, Watch, WatchAck,
 };
-use crate::module::Module;
+use crate::{module::Module, subable::Subable};
 
 mod error;
 pub use error::{Error, Result};
@@ -27,23 +33,149 @@ pub use error::{Error, Result};
 mod topic;
 use topic::Topic;
 
+mod subscription;
+use subscription::Subscription;
+
+mod tee;
+use tee::{Taps, Tee};
+
 mod request;
 pub use request::Request;
 
+mod handler;
+pub use handler::{Filter, Handler, HandlerSet, Reconfiguration};
+
+mod guard;
+pub use guard::HandlerGuard;
+use guard::Uninstalls;
+
+mod reconnect;
+pub use reconnect::{Backoff, ReconnectingEngine};
+
+mod slots;
+use slots::Slots;
+
+mod ratelimit;
+use ratelimit::{RateLimit, Verdict};
+
+mod timer;
+use timer::{SystemTimer, Timer};
+
+mod split;
+pub use split::{EngineReader, EngineWriter};
+
+mod handle;
+pub use handle::EngineHandle;
+
+mod local;
+pub use local::EngineInfo;
+
+mod metrics;
+pub use metrics::EngineMetrics;
+use metrics::Metrics;
+
+#[cfg(windows)]
+mod pipe;
+
+#[cfg(feature = "tracing-subscriber")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing-subscriber")))]
+mod layer;
+#[cfg(feature = "tracing-subscriber")]
+pub use layer::EngineLayer;
+
+#[cfg(test)]
+mod tests;
+
 /// A connector to the telephony engine.
 pub struct Engine<I, O>
 where
     I: AsyncRead + Send + Unpin,
     O: AsyncWrite + Send + Unpin,
 {
-    rx: Subable<Lines<BufReader<I>>, Topic>,
+    rx: Subable<Tee<I>, Topic>,
+    taps: Arc<Taps>,
     tx: Mutex<O>,
+
+    watchdog: Option<Duration>,
+    timeout: Option<Duration>,
+    connect_grace: Duration,
+    delta_acks: bool,
+    last_recv: sync::Mutex<Instant>,
+    keepalive_keywords: Vec<String>,
+    keepalives: AtomicU64,
+    ids: AtomicU64,
+    id_source: Option<sync::Mutex<IdSource>>,
+    max_line: AtomicUsize,
+    clock: fn() -> SystemTime,
+    timer: Box<dyn Timer>,
+    last_time: sync::Mutex<SystemTime>,
+    handlers: sync::Mutex<HandlerSet>,
+    uninstalls: sync::Mutex<Uninstalls>,
+    slots: Option<Slots>,
+    dropped: AtomicU64,
+    dropped_limit: RateLimit,
+    unhandled: Option<UnhandledHook>,
+    info: sync::OnceLock<EngineInfo>,
+    metrics: Metrics,
+}
+
+/// Wrap an error with the context of the command of the `message` that failed.
+fn failed<T: Facet<'static>>(_message: &T) -> impl FnOnce(Error) -> Error {
+    let command = T::SHAPE
+        .type_tag
+        .map_or("unknown", |tag| tag.trim_start_matches(['%', '>', '<']));
+
+    move |source| Error::Request {
+        command,
+        source: Box::new(source),
+    }
 }
 
+/// Write all the `bufs` to the writer, in as few writes as it's vectored I/O allows.
+async fn write_all_vectored<O: AsyncWrite + Unpin>(
+    wr: &mut O,
+    mut bufs: &mut [IoSlice<'_>],
+) -> io::Result<()> {
+    while !bufs.is_empty() {
+        match wr.write_vectored(bufs).await? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            len => IoSlice::advance_slices(&mut bufs, len),
+        }
+    }
+
+    Ok(())
+}
+
+/// A generator of message ids, see [`Engine::with_id_source`].
+type IdSource = Box<dyn FnMut() -> String + Send>;
+
+/// A callback receiving the raw unhandled lines, see [`Engine::on_unhandled`].
+type UnhandledHook = Box<dyn Fn(&str) + Send + Sync>;
+
 impl Engine<AllowStdIo<Stdin>, AllowStdIo<Stdout>> {
     /// Initialize a connection to the engine via standard I/O.
+    ///
+    /// As reading the standard input blocks, the grace period of [`Self::connect`] isn't waited for.
     pub fn stdio() -> Self {
         Self::from_io(AllowStdIo::new(io::stdin()), AllowStdIo::new(io::stdout()))
+            .with_connect_grace(Duration::ZERO)
+    }
+}
+
+impl Engine<AllowStdIo<File>, AllowStdIo<File>> {
+    /// Initialize a connection to a recorded transcript, reading the lines of the engine
+    /// from the `input` file and appending the sent lines to the `output` file, created if missing.
+    ///
+    /// The engine is considered gone once the end of the `input` file is reached,
+    /// and the grace period of [`Self::connect`] isn't waited for.
+    pub fn from_files(input: impl AsRef<Path>, output: impl AsRef<Path>) -> io::Result<Self> {
+        let input = File::open(input)?;
+        let output = OpenOptions::new().create(true).append(true).open(output)?;
+
+        Ok(
+            Self::from_io(AllowStdIo::new(input), AllowStdIo::new(output))
+                .with_connect_grace(Duration::ZERO),
+        )
     }
 }
 
@@ -57,179 +189,653 @@ where
     /// If the I/O is a socket or a TCP stream, the module must register itself
     /// with a [`Self::connect`] before doing anything.
     pub fn from_io(rx: I, tx: O) -> Self {
+        let taps = Arc::new(Taps::default());
+
         Self {
-            rx: Subable::new(BufReader::new(rx).lines()),
+            rx: Subable::new(Tee::new(rx, taps.clone())),
+            taps,
             tx: tx.into(),
+
+            watchdog: None,
+            timeout: None,
+            connect_grace: Self::DEFAULT_CONNECT_GRACE,
+            delta_acks: false,
+            last_recv: Instant::now().into(),
+            keepalive_keywords: Default::default(),
+            keepalives: Default::default(),
+            ids: Default::default(),
+            id_source: None,
+            max_line: AtomicUsize::new(Self::DEFAULT_BUFSIZE),
+            clock: SystemTime::now,
+            timer: Box::new(SystemTimer),
+            last_time: SystemTime::UNIX_EPOCH.into(),
+            handlers: Default::default(),
+            uninstalls: Default::default(),
+            slots: None,
+            dropped: Default::default(),
+            dropped_limit: RateLimit::new(10, Duration::from_secs(60)),
+            unhandled: None,
+            info: Default::default(),
+            metrics: Default::default(),
         }
     }
 
+    /// The default size of the engine's line buffer, the `bufsize` _local variable_.
+    pub const DEFAULT_BUFSIZE: usize = 8192;
+
+    /// Limit the length of the lines sent to the engine to less than `bufsize`,
+    /// the size of the engine's line buffer, [`Self::DEFAULT_BUFSIZE`] by default.
+    ///
+    /// Sending a longer line fails with [`Error::LineTooLong`], the limit follows
+    /// the changes of the `bufsize` _local variable_ made with [`Self::setlocal`].
+    ///
+    /// # Panics
+    ///
+    /// If `bufsize` is `0`, the buffer must at least hold the line's terminating newline.
+    pub fn with_bufsize(self, bufsize: usize) -> Self {
+        assert!(bufsize > 0, "the engine's line buffer can't be empty");
+
+        self.max_line.store(bufsize, Ordering::Relaxed);
+        self
+    }
+
+    /// Enable the _half-open connection_ watchdog with the provided `interval`.
+    ///
+    /// When sending while no line has been received from the engine for longer than `interval`,
+    /// the engine is probed with a `getlocal`, and if it doesn't answer within `interval`
+    /// the link is considered dead and [`Error::LinkDead`] is returned.
+    pub fn with_watchdog(mut self, interval: Duration) -> Self {
+        self.watchdog = Some(interval);
+        self
+    }
+
+    /// Limit the time waiting for the engine to acknowledge a request to `timeout`,
+    /// after which [`Error::Timeout`] is returned.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The default time waited for the engine to slam the connection after a [`Self::connect`].
+    pub const DEFAULT_CONNECT_GRACE: Duration = Duration::from_millis(100);
+
+    /// Wait up to `grace` after sending a [`Self::connect`] for the engine to slam the connection,
+    /// [`Self::DEFAULT_CONNECT_GRACE`] by default, or not at all with [`Duration::ZERO`].
+    pub fn with_connect_grace(mut self, grace: Duration) -> Self {
+        self.connect_grace = grace;
+        self
+    }
+
+    /// Only send the parameters changed while processing a message when acknowledging it
+    /// with [`Self::ack`], instead of all of them, to reduce the size of the acknowledgements.
+    ///
+    /// The engine keeps the parameters absent from an acknowledgement unchanged,
+    /// so this doesn't change the resulting message, as long as no parameter was removed:
+    /// a removed parameter can't be represented and is kept by the engine either way.
+    pub fn with_delta_acks(mut self) -> Self {
+        self.delta_acks = true;
+        self
+    }
+
+    /// Consider the lines with the `keyword` as _keepalives_ in addition to blank lines,
+    /// for the engine configurations emitting periodic no-op lines, such as `%%>keepalive`.
+    ///
+    /// Keepalives are silently dropped and counted in [`Self::keepalives`].
+    pub fn with_keepalive_keyword(mut self, keyword: impl Into<String>) -> Self {
+        self.keepalive_keywords.push(keyword.into());
+        self
+    }
+
+    /// Generate the ids of the messages sent with `source` instead of the default scheme,
+    /// to make them predictable in tests, the generated ids must be unique and non-empty.
+    pub fn with_id_source(mut self, source: impl FnMut() -> String + Send + 'static) -> Self {
+        self.id_source = Some(sync::Mutex::new(Box::new(source)));
+        self
+    }
+
+    /// Call `callback` with the raw lines received from the engine that are unhandled and dropped,
+    /// instead of logging them with a warning.
+    pub fn on_unhandled(mut self, callback: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.unhandled = Some(Box::new(callback));
+        self
+    }
+
+    /// The count of _keepalive_ lines received from the engine,
+    /// blank or with a keyword registered with [`Self::with_keepalive_keyword`].
+    pub fn keepalives(&self) -> u64 {
+        self.keepalives.load(Ordering::Relaxed)
+    }
+
+    /// A snapshot of the counters of the traffic exchanged with the engine.
+    pub fn metrics(&self) -> EngineMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// The count of unhandled lines received from the engine, that were dropped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn is_keepalive(&self, recvd: &str) -> bool {
+        let keyword = recvd.split(':').next().unwrap_or_default().trim();
+
+        self.keepalive_keywords.iter().any(|kw| kw == keyword)
+    }
+
     async fn default_response(&self, recvd: &str) -> Result<()> {
-        if let Ok(Message {
-            id, retvalue, kv, ..
-        }) = wire::from_str(recvd)
-        {
-            self.send(&MessageAck {
-                id,
-                processed: false,
-                name: None,
-                retvalue,
-                kv,
-            })
-            .await
-        } else if let Ok(ErrorIn { original }) = wire::from_str(recvd) {
-            tracing::error!("received an error: {original}");
+        if self.uninstalled(recvd) {
+            return Ok(());
+        }
+
+        match wire::from_str(recvd) {
+            Ok(Message { id, .. }) if id.is_empty() => {
+                tracing::trace!("dropping an unhandled id-less message: {recvd}");
 
-            // FIXME: treat error case with a correct topic
+                Ok(())
+            }
+            Ok(Message {
+                id, retvalue, kv, ..
+            }) => {
+                self.write(&MessageAck {
+                    id,
+                    processed: false,
+                    name: None,
+                    retvalue,
+                    kv,
+                })
+                .await
+            }
+            Err(err) if matches!(err, wire::Error::EmptyInput) || self.is_keepalive(recvd) => {
+                tracing::trace!("received a keepalive");
+                self.keepalives.fetch_add(1, Ordering::Relaxed);
 
-            Ok(())
-        } else {
-            tracing::warn!("unhandled message, dropped: {recvd}");
+                Ok(())
+            }
+            Err(_) => {
+                if let Some(ErrorIn { original }) = ErrorIn::parse(recvd) {
+                    tracing::error!("received an error: {original}");
+                } else {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+
+                    if let Some(unhandled) = &self.unhandled {
+                        unhandled(recvd);
+                    } else {
+                        match self.dropped_limit.hit(self.timer.now()) {
+                            Verdict::Log { suppressed: 0 } => {
+                                tracing::warn!("unhandled message, dropped: {recvd}")
+                            }
+                            Verdict::Log { suppressed } => tracing::warn!(
+                                "unhandled message, dropped: {recvd} ({suppressed} more were dropped since last report)"
+                            ),
+                            Verdict::Suppress => (),
+                        }
+                    }
+                }
 
-            Ok(())
+                Ok(())
+            }
         }
     }
 
     #[tracing::instrument(skip(self))]
-    fn subscribe<T: Facet<'static>>(&self, topic: Topic) -> impl TryStream<Ok = T, Error = Error> {
-        let queue = self.rx.subscribe(topic);
-
-        futures::stream::try_unfold(queue, async |mut queue| {
-            loop {
-                match queue.try_next().await? {
-                    None => break Ok(None),
-                    Some(Item::Unhandled(recvd)) => self.default_response(&recvd).await?,
-                    Some(Item::Subscribed(recvd)) => {
-                        break Ok(Some((wire::from_str(&recvd)?, queue)));
-                    }
-                }
+    fn subscribe<T: for<'de> Facet<'de>>(&self, topic: Topic) -> Subscription<'_, I, O, T> {
+        Subscription::new(self, topic)
+    }
+
+    /// Subscribe to the `topic` for exactly one item, the subscription being
+    /// registered right away and dropped once the item is received.
+    fn subscribe_once<T: for<'de> Facet<'de>>(
+        &self,
+        topic: Topic,
+    ) -> impl Future<Output = Result<T>> + '_ {
+        let mut sub = self.subscribe::<T>(topic);
+
+        async move { sub.try_next().await?.ok_or(Error::UnexpectedEof) }
+    }
+
+    async fn probe(&self, interval: Duration) -> Result<()> {
+        let message = SetLocal {
+            name: "engine.runid".into(),
+            value: None,
+        };
+
+        let ack = self.subscribe_once::<SetLocalAck>(Topic::SetLocalAck(message.name.clone()));
+        self.write(&message).await?;
+
+        match future::select(pin!(ack), self.timer.delay(interval)).await {
+            Either::Left((ack, _)) => ack.map(drop),
+            Either::Right(_) => Err(Error::LinkDead),
+        }
+    }
+
+    /// Measure the round-trip time to the engine with a cheap `getlocal` of `engine.runid`,
+    /// as a liveness probe of the connection not requiring any handler.
+    ///
+    /// This fails with [`Error::Timeout`] if the engine didn't answer within `timeout`.
+    pub async fn ping(&self, timeout: Duration) -> Result<Duration> {
+        let start = self.timer.now();
+
+        match self.probe(timeout).await {
+            Ok(()) => Ok(self.timer.now().saturating_duration_since(start)),
+            Err(Error::LinkDead) => Err(Error::Timeout),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Wait for the engine to be ready to handle commands, for at most `timeout`.
+    ///
+    /// This is especially useful after a [`Self::connect`], where the engine sets up
+    /// the connection role before it's able to answer commands: the engine is probed
+    /// with a `getlocal` which is retried until answered.
+    pub async fn await_ready(&self, timeout: Duration) -> Result<()> {
+        const RETRY: Duration = Duration::from_millis(100);
+
+        let deadline = self.timer.now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(self.timer.now());
+            if remaining.is_zero() {
+                break Err(Error::Timeout);
+            }
+
+            match self.probe(remaining.min(RETRY)).await {
+                Err(Error::LinkDead) => tracing::debug!("engine isn't ready yet, retrying"),
+                other => break other,
             }
-        })
-        .boxed() // FIXME: maybe remove this `Box`
+        }
+    }
+
+    /// Limit the count of concurrent operations waiting for an acknowledgement from the engine,
+    /// further operations wait for a slot to be freed before being sent.
+    pub fn set_max_pending_acks(&mut self, max: usize) {
+        self.slots = Some(Slots::new(max));
+    }
+
+    async fn request<T: for<'de> Facet<'de>>(
+        &self,
+        message: &impl Facet<'static>,
+        topic: Topic,
+    ) -> Result<T> {
+        self.exchange(message, topic).await.map_err(failed(message))
+    }
+
+    async fn exchange<T: for<'de> Facet<'de>>(
+        &self,
+        message: &impl Facet<'static>,
+        topic: Topic,
+    ) -> Result<T> {
+        let _slot = match &self.slots {
+            Some(slots) => Some(slots.acquire().await),
+            None => None,
+        };
+
+        // Subscribe before sending, so an early acknowledgement can't be missed
+        let ack = self.subscribe_once::<T>(topic);
+        self.check_link().await?;
+        self.write(message).await?;
+
+        match self.timeout {
+            Some(timeout) => match future::select(pin!(ack), self.timer.delay(timeout)).await {
+                Either::Left((ack, _)) => ack,
+                Either::Right(_) => Err(Error::Timeout),
+            },
+            None => ack.await,
+        }
     }
 
     async fn send<T: Facet<'static>>(&self, message: &T) -> Result<()> {
-        let item = wire::to_string(message);
+        let sent = async {
+            self.check_link().await?;
+
+            self.write(message).await
+        };
+
+        sent.await.map_err(failed(message))
+    }
+
+    /// Send an already formatted `line` to the engine, for commands not modeled by this crate.
+    ///
+    /// The `line` is written as-is, without the trailing newline: the caller is responsible
+    /// for it's correct formatting and escaping, see [`wire::upcode`].
+    pub async fn raw_send(&self, line: impl AsRef<str>) -> Result<()> {
+        self.check_link().await?;
+
+        self.write_raw(line.as_ref()).await
+    }
+
+    async fn check_link(&self) -> Result<()> {
+        if let Some(interval) = self.watchdog {
+            let elapsed = self
+                .timer
+                .now()
+                .saturating_duration_since(*self.last_recv.lock().unwrap());
+
+            if elapsed > interval {
+                tracing::debug!("no line received for {elapsed:?}, probing the link");
+
+                self.probe(interval).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write<T: Facet<'static>>(&self, message: &T) -> Result<()> {
+        self.write_raw(&wire::try_to_string(message)?).await
+    }
+
+    async fn write_raw(&self, line: &str) -> Result<()> {
+        self.write_lines(&[line]).await
+    }
+
+    async fn write_lines(&self, lines: &[&str]) -> Result<()> {
+        // Check all the lines beforehand, so that none of them is sent if one is too long
+        let max = self.max_line.load(Ordering::Relaxed);
+        if let Some(line) = lines.iter().find(|line| line.len() >= max) {
+            return Err(Error::LineTooLong {
+                len: line.len(),
+                max,
+            });
+        }
 
         let mut wr = self.tx.lock().await;
-        wr.write_all(item.as_bytes()).await?;
-        wr.write_all(b"\n").await?;
+
+        // Write the uninstalls queued by dropped handler guards first,
+        // only dequeuing them once written so a failed write doesn't lose them
+        let names = self.uninstalls.lock().unwrap().queued.clone();
+        let uninstalls = names
+            .iter()
+            .map(|name| wire::to_string(&Uninstall { name: name.clone() }))
+            .collect::<Vec<_>>();
+
+        // Write the lines and their terminators as separate slices, without joining them
+        let mut bufs = uninstalls
+            .iter()
+            .map(String::as_str)
+            .chain(lines.iter().copied())
+            .flat_map(|line| [IoSlice::new(line.as_bytes()), IoSlice::new(b"\n")])
+            .collect::<Vec<_>>();
+        write_all_vectored(&mut *wr, &mut bufs).await?;
+
+        for line in uninstalls
+            .iter()
+            .map(String::as_str)
+            .chain(lines.iter().copied())
+        {
+            self.metrics.sent(line);
+        }
+
+        // The guards dropped meanwhile queued their uninstall after the written ones
+        {
+            let mut uninstalls = self.uninstalls.lock().unwrap();
+            let written = uninstalls.queued.drain(..names.len()).collect::<Vec<_>>();
+            uninstalls.sent.extend(written);
+        }
 
         wr.flush().await.map_err(Into::into)
     }
 
+    /// Send the `messages` to the engine in order, flushing the connection only once,
+    /// the engine not answering them with an acknowledgement.
+    ///
+    /// If writing fails, the error is returned and the remaining messages are not sent,
+    /// while the previous ones may have been sent.
+    pub async fn send_batch<T: Unacknowledged>(&self, messages: &[T]) -> Result<()> {
+        self.check_link().await?;
+
+        let lines = messages
+            .iter()
+            .map(wire::try_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.write_lines(&lines.iter().map(String::as_str).collect::<Vec<_>>())
+            .await
+    }
+
     /// Attach a [`Module`] to the engine to process messages and watches.
     ///
     /// This is a handy helper to abstract the management of message requests
-    /// and ensure they are always acknowledged while reducing boilerplate code.
+    /// and ensure they are always acknowledged while reducing boilerplate code,
+    /// the module is driven with [`Module::run`].
     pub async fn attach<M: Module>(self, module: M) -> Result<(), M::Error> {
-        futures::try_join!(
-            self.watches()
-                .err_into::<M::Error>()
-                .try_for_each_concurrent(None, |watch| { module.on_watch(&self, watch) }),
-            self.messages()
-                .err_into::<M::Error>()
-                .try_for_each_concurrent(None, async |mut req| {
-                    let processed = module.on_message(&self, &mut req).await?;
-
-                    Ok(self.ack(req, processed).await?)
-                }),
-            module.install(&self)
-        )?;
-
-        tracing::debug!("processed all messages, exiting");
-
-        Ok(())
+        module.run(&self).await
     }
 
     /// Request the engine to install a message handler with the provided `priority`.
     pub async fn install(
         &self,
         priority: impl Into<Option<u64>>,
-        name: impl Into<String>,
+        name: impl Into<MessageName>,
         filter: impl Into<Option<(String, Option<String>)>>,
     ) -> Result<bool> {
+        self.install_full(priority, name, filter)
+            .await
+            .map(|ack| ack.success)
+    }
+
+    /// Request the engine to install a message handler, see [`Self::install`],
+    /// returning the whole acknowledgement with the priority actually assigned by the engine.
+    pub async fn install_full(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<InstallAck> {
         let message = Install {
             priority: priority.into(),
-            name: name.into(),
+            name: name.into().into(),
             filter: filter.into(),
         };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<InstallAck>(Topic::InstallAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<InstallAck>(&message, Topic::InstallAck(message.name.clone()))
+            .await?;
 
-        Ok(ack.success)
+        if ack.success {
+            self.handlers.lock().unwrap().insert(
+                message.name,
+                Handler {
+                    priority: message.priority,
+                    filter: message.filter,
+                },
+            );
+        }
+
+        Ok(ack)
+    }
+
+    /// Request the engine to install the message handlers of `installs` in order like [`Self::install_full`],
+    /// flushing the connection only once, and returning their acknowledgements in the same order.
+    ///
+    /// If writing fails, the error is returned and none of the handlers is considered installed,
+    /// while some of them may have been installed.
+    pub async fn install_batch(
+        &self,
+        installs: impl IntoIterator<Item = Install>,
+    ) -> Result<Vec<InstallAck>> {
+        let installs = installs.into_iter().collect::<Vec<_>>();
+
+        // Subscribe before sending, so an early acknowledgement can't be missed
+        let acks = future::try_join_all(installs.iter().map(|install| {
+            self.subscribe_once::<InstallAck>(Topic::InstallAck(install.name.clone()))
+        }));
+        self.check_link().await?;
+
+        let lines = installs
+            .iter()
+            .map(wire::try_to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.write_lines(&lines.iter().map(String::as_str).collect::<Vec<_>>())
+            .await?;
+
+        let acks = match self.timeout {
+            Some(timeout) => match future::select(pin!(acks), self.timer.delay(timeout)).await {
+                Either::Left((acks, _)) => acks,
+                Either::Right(_) => Err(Error::Timeout),
+            },
+            None => acks.await,
+        }?;
+
+        let mut handlers = self.handlers.lock().unwrap();
+        for (install, _) in installs
+            .into_iter()
+            .zip(&acks)
+            .filter(|(_, ack)| ack.success)
+        {
+            handlers.insert(
+                install.name,
+                Handler {
+                    priority: install.priority,
+                    filter: install.filter,
+                },
+            );
+        }
+
+        Ok(acks)
     }
 
     /// Request the engine to remove a previously installed handler.
-    pub async fn uninstall(&self, name: impl Into<String>) -> Result<bool> {
-        let message = Uninstall { name: name.into() };
+    pub async fn uninstall(&self, name: impl Into<MessageName>) -> Result<bool> {
+        let message = Uninstall {
+            name: name.into().into(),
+        };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<UninstallAck>(Topic::UninstallAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<UninstallAck>(&message, Topic::UninstallAck(message.name.clone()))
+            .await?;
+
+        if ack.success {
+            self.handlers.lock().unwrap().remove(&message.name);
+        }
 
         Ok(ack.success)
     }
 
+    /// The set of handlers currently installed through this engine.
+    pub fn handlers(&self) -> HandlerSet {
+        self.handlers.lock().unwrap().clone()
+    }
+
+    /// Reconfigure the installed handlers to match the `desired` set,
+    /// only issuing the operations necessary to go from the current set to the `desired` one.
+    pub async fn reconfigure(&self, desired: HandlerSet) -> Result<Reconfiguration> {
+        let current = self.handlers();
+        let mut report = Reconfiguration::default();
+
+        for name in current.keys().filter(|name| !desired.contains_key(*name)) {
+            if self.uninstall(name).await? {
+                report.uninstalled.push(name.clone());
+            } else {
+                report.failed.push(name.clone());
+            }
+        }
+
+        for (name, handler) in &desired {
+            let reinstall = match current.get(name) {
+                Some(installed) if installed == handler => continue,
+                Some(_) => {
+                    if !self.uninstall(name).await? {
+                        report.failed.push(name.clone());
+                        continue;
+                    }
+
+                    true
+                }
+                None => false,
+            };
+
+            if !self
+                .install(handler.priority, name, handler.filter.clone())
+                .await?
+            {
+                report.failed.push(name.clone());
+            } else if reinstall {
+                report.reinstalled.push(name.clone());
+            } else {
+                report.installed.push(name.clone());
+            }
+        }
+
+        Ok(report)
+    }
+
     /// Request the engine to install a message watcher.
-    pub async fn watch(&self, name: impl Into<String>) -> Result<bool> {
-        let message = Watch { name: name.into() };
+    pub async fn watch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        let message = Watch {
+            name: name.into().into(),
+        };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<WatchAck>(Topic::WatchAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<WatchAck>(&message, Topic::WatchAck(message.name.clone()))
+            .await?;
 
         Ok(ack.success)
     }
 
     /// Request the engine to remove a previously installed watcher.
-    pub async fn unwatch(&self, name: impl Into<String>) -> Result<bool> {
-        let message = Unwatch { name: name.into() };
+    pub async fn unwatch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        let message = Unwatch {
+            name: name.into().into(),
+        };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<UnwatchAck>(Topic::UnwatchAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<UnwatchAck>(&message, Topic::UnwatchAck(message.name.clone()))
+            .await?;
 
         Ok(ack.success)
     }
 
     /// Receive _watches_ from the telephony engine.
+    ///
+    /// Watches are post-dispatch notifications, sent as a [`MessageAck`]:
+    /// any acknowledgement whose `id` doesn't match a pending [`Self::message`] call
+    /// is considered a watch.
     pub fn watches(&self) -> impl TryStream<Ok = MessageAck, Error = Error> {
         self.subscribe(Topic::Watch)
     }
 
     /// Request the engine to set a _local variable_.
+    ///
+    /// The `value` of known typed variables (like `timeout` or `timebomb`)
+    /// is validated before sending, returning [`Error::InvalidParamValue`] if invalid.
     pub async fn setlocal(
         &self,
         name: impl Into<String>,
         value: impl Into<String>,
     ) -> Result<bool> {
+        self.setlocal_full(name, value)
+            .await
+            .map(|(success, _)| success)
+    }
+
+    /// Request the engine to set a _local variable_, see [`Self::setlocal`],
+    /// returning the value actually applied by the engine along with the success.
+    pub async fn setlocal_full(
+        &self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(bool, String)> {
         let message = SetLocal {
             name: name.into(),
             value: Some(value.into()),
         };
 
-        self.send(&message).await?;
+        if let Some(value) = &message.value {
+            local::validate(&message.name, value)?;
+        }
+
         let ack = self
-            .subscribe::<SetLocalAck>(Topic::SetLocalAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<SetLocalAck>(&message, Topic::SetLocalAck(message.name.clone()))
+            .await?;
 
-        Ok(ack.success)
+        // Follow the size of the engine's line buffer
+        if ack.success
+            && ack.name == "bufsize"
+            && let Ok(bufsize) = ack.value.parse()
+            && bufsize > 0
+        {
+            self.max_line.store(bufsize, Ordering::Relaxed);
+        }
+
+        Ok((ack.success, ack.value))
     }
 
     /// Request the value of a _local variable_.
@@ -239,69 +845,240 @@ where
             value: None,
         };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<SetLocalAck>(Topic::SetLocalAck(message.name))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<SetLocalAck>(&message, Topic::SetLocalAck(message.name.clone()))
+            .await?;
 
         Ok(ack.value)
     }
 
-    fn id() -> String {
-        let id = (0..12)
-            .map(|_| fastrand::alphanumeric())
-            .collect::<String>();
+    /// Generate a message id, unique for the life of the engine.
+    fn id(&self) -> String {
+        if let Some(source) = &self.id_source {
+            return (source.lock().unwrap())();
+        }
+
+        let seq = self.ids.fetch_add(1, Ordering::Relaxed);
+
+        format!("{}.{}.{seq}", env!("CARGO_PKG_NAME"), std::process::id())
+    }
+
+    /// The current time, never going backward from the previously returned one,
+    /// so message timestamps stay monotonic if the system clock is stepped back.
+    fn now(&self) -> SystemTime {
+        let mut last = self.last_time.lock().unwrap();
+        *last = (self.clock)().max(*last);
 
-        format!("{}.{id}", env!("CARGO_PKG_NAME"))
+        *last
     }
 
     /// Send a [`Message`] to the telephony engine for processing.
     pub async fn message(
         &self,
-        name: impl Into<String>,
+        name: impl Into<MessageName>,
         retvalue: impl Into<String>,
-        kv: BTreeMap<String, String>,
-    ) -> Result<(bool, String, BTreeMap<String, String>)> {
-        let id = Self::id();
+        kv: Params,
+    ) -> Result<(bool, String, Params)> {
+        let id = self.id();
         let message = Message {
             id,
-            time: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("time went backward, run you foul >:(")
-                .as_secs(),
-            name: name.into(),
+            time: self.now(),
+            name: name.into().into(),
             retvalue: retvalue.into(),
             kv,
         };
 
-        self.send(&message).await?;
         let ack = self
-            .subscribe::<MessageAck>(Topic::MessageAck(message.id))
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+            .request::<MessageAck>(&message, Topic::MessageAck(message.id.clone()))
+            .await?;
 
         Ok((ack.processed, ack.retvalue, ack.kv))
     }
 
+    /// Wrap the received messages in a [`Request`], tracking their parameters for [`Self::with_delta_acks`].
+    fn to_request(&self) -> impl Fn(Message) -> Request + use<I, O> {
+        let delta = self.delta_acks;
+
+        move |message| {
+            if delta {
+                Request::tracked(message)
+            } else {
+                Request::new(message)
+            }
+        }
+    }
+
     /// Receive _messages_ from the telephony engine for processing.
+    ///
+    /// When multiple streams are subscribed concurrently, only the oldest one still subscribed
+    /// receives the messages, the others only receiving them once it's dropped: to process messages
+    /// concurrently, dispatch them from a single stream, or split them with [`Self::messages_named`]
+    /// or [`Self::messages_matching`].
+    ///
+    /// The messages received while the stream isn't polled are kept for it, however long it
+    /// takes to poll it again, but past a few of them the other streams and requests wait for it:
+    /// await requests concurrently with the stream when messages may keep arriving meanwhile.
     pub fn messages(&self) -> impl TryStream<Ok = Request, Error = Error> {
-        self.subscribe(Topic::Message).map_ok(Request::new)
+        self.subscribe(Topic::Message).map_ok(self.to_request())
+    }
+
+    /// Receive _messages_ named `name` from the telephony engine for processing.
+    ///
+    /// The messages with this name are then not yielded by [`Self::messages`],
+    /// letting distinct tasks own distinct message names.
+    pub fn messages_named(
+        &self,
+        name: impl Into<MessageName>,
+    ) -> impl TryStream<Ok = Request, Error = Error> {
+        self.subscribe(Topic::MessageNamed(name.into().into()))
+            .map_ok(self.to_request())
+    }
+
+    /// Receive the _messages_ satisfying the `predicate` from the telephony engine for processing,
+    /// for matches not expressible with a message name, like on the value of a parameter.
+    ///
+    /// The messages rejected by the `predicate` are yielded by the other [`Self::messages`] streams,
+    /// as if this one wasn't subscribed, while the ones satisfying it are only yielded
+    /// here if this stream was subscribed before the others.
+    pub fn messages_matching(
+        &self,
+        predicate: impl Fn(&Message) -> bool + Send + Sync + 'static,
+    ) -> impl TryStream<Ok = Request, Error = Error> {
+        Subscription::filtered(self, Topic::Message, predicate).map_ok(self.to_request())
+    }
+
+    /// Whether a [`Self::messages`] stream is currently receiving messages.
+    pub fn is_receiving_messages(&self) -> bool {
+        self.rx.is_subscribed(&Topic::Message)
+    }
+
+    /// Close the [`Self::messages`], [`Self::messages_named`], [`Self::messages_matching`] and
+    /// [`Self::heartbeat`] streams, the messages received afterwards are acknowledged as not processed,
+    /// so they are passed to the next handler.
+    pub fn stop_messages(&self) {
+        self.rx
+            .unsubscribe_matching(|topic| matches!(topic, Topic::Message | Topic::MessageNamed(_)));
+    }
+
+    /// The count of distinct subscriptions currently waiting on the engine,
+    /// to audit leaked requests or streams.
+    pub fn active_subscriptions(&self) -> usize {
+        self.rx.active_topics()
+    }
+
+    /// Observe every line received from the engine, including the ones routed to
+    /// the other subscriptions, like [`Self::messages`].
+    ///
+    /// The lines are copied as they are read for the other subscriptions,
+    /// this stream doesn't read from the engine on it's own and ends along with the connection.
+    ///
+    /// Up to [`Self::RAW_LINES_CAPACITY`] lines are buffered for a stream not keeping up,
+    /// the lines past this capacity are dropped for it and counted in [`Self::raw_lines_dropped`].
+    pub fn raw_lines(&self) -> impl Stream<Item = Result<String>> + use<I, O> {
+        self.taps.tap(Self::RAW_LINES_CAPACITY).map(Ok)
+    }
+
+    /// The count of lines buffered for a [`Self::raw_lines`] stream, past which they are dropped.
+    pub const RAW_LINES_CAPACITY: usize = 1024;
+
+    /// The count of lines dropped for the [`Self::raw_lines`] streams not keeping up.
+    pub fn raw_lines_dropped(&self) -> u64 {
+        self.taps.dropped()
+    }
+
+    /// Process the _messages_ named `name` with the `handler`,
+    /// acknowledging each of them with the returned `processed` value.
+    ///
+    /// When the `handler` errors, the message is acknowledged as not processed,
+    /// so it is passed to the next handler, and the error is returned.
+    /// The handler for `name` must be installed beforehand with [`Self::install`].
+    pub async fn handle<E: From<Error>>(
+        &self,
+        name: impl Into<MessageName>,
+        mut handler: impl AsyncFnMut(&mut Request) -> Result<bool, E>,
+    ) -> Result<(), E> {
+        let mut messages = self.messages_named(name);
+        while let Some(mut req) = messages.try_next().await? {
+            match handler(&mut req).await {
+                Ok(processed) => self.ack(req, processed).await?,
+                Err(err) => {
+                    self.ack(req, false).await?;
+
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Install a handler for the `engine.timer` messages with the provided `priority`,
+    /// calling `tick` for each of them and acknowledging them as not processed,
+    /// so they are passed to the next handler.
+    ///
+    /// The timer messages are then not yielded by [`Self::messages`].
+    /// This returns `false` if the handler couldn't be installed,
+    /// or `true` when the engine stops sending messages.
+    pub async fn heartbeat(
+        &self,
+        priority: impl Into<Option<u64>>,
+        mut tick: impl FnMut(&Message),
+    ) -> Result<bool> {
+        if !self
+            .install(priority, MessageName::EngineTimer, None)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        let mut timers = self.messages_named(MessageName::EngineTimer);
+        while let Some(req) = timers.try_next().await? {
+            tick(&req);
+
+            self.ack(req, false).await?;
+        }
+
+        Ok(true)
     }
 
     /// Acknowledge the message from the engine,
     /// letting it forward it to the next handler if `!processed`.
-    pub async fn ack(&self, req: Request, processed: bool) -> Result<()> {
+    ///
+    /// With [`Self::with_delta_acks`], only the parameters changed since reception are sent.
+    pub async fn ack(&self, mut req: Request, processed: bool) -> Result<()> {
+        let retvalue = std::mem::take(&mut req.retvalue);
+        let mut kv = std::mem::take(&mut req.kv);
+        if let Some(original) = req.original.take() {
+            kv.retain(|key, value| original.get(key) != Some(value));
+        }
+
+        self.ack_with(req, processed, retvalue, kv).await
+    }
+
+    /// Acknowledge the message from the engine with the provided `retvalue` and `kv`,
+    /// letting it forward it to the next handler if `!processed`.
+    ///
+    /// A message without an `id` doesn't expect an answer, nothing is then sent.
+    pub async fn ack_with(
+        &self,
+        req: Request,
+        processed: bool,
+        retvalue: impl Into<String>,
+        kv: Params,
+    ) -> Result<()> {
         let original = req.into_inner();
+        if original.id.is_empty() {
+            tracing::trace!("not acknowledging id-less message `{}`", original.name);
+
+            return Ok(());
+        }
 
         let message = MessageAck {
             id: original.id,
             processed,
             name: Some(original.name),
-            retvalue: original.retvalue,
-            kv: original.kv,
+            retvalue: retvalue.into(),
+            kv,
         };
 
         self.send(&message).await
@@ -309,17 +1086,38 @@ where
 
     /// Send a _connect_ message to the engine for
     /// _socket-based_ modules.
+    ///
+    /// The `channel` must be provided for the roles attaching to a channel's data,
+    /// and omitted otherwise, see [`ConnectRole::has_channel`],
+    /// or [`Error::InvalidConnectRole`] is returned without sending anything.
+    ///
+    /// The engine doesn't answer this request, but slams the connection shut if it fails:
+    /// the connection is then watched for the grace period set with [`Self::with_connect_grace`],
+    /// and [`Error::ConnectRejected`] is returned if it's closed meanwhile.
     pub async fn connect(
         &self,
         role: ConnectRole,
         channel: impl Into<Option<(String, Option<String>)>>,
     ) -> Result<()> {
-        let message = Connect {
-            role,
-            channel: channel.into(),
-        };
+        let channel = channel.into();
+        if role.has_channel() != channel.is_some() {
+            return Err(Error::InvalidConnectRole(role));
+        }
 
-        self.send(&message).await
+        let message = Connect { role, channel };
+        self.send(&message).await?;
+
+        if !self.connect_grace.is_zero() {
+            let slammed = pin!(self.rx.peek_ended());
+
+            if let Either::Left((true, _)) =
+                future::select(slammed, self.timer.delay(self.connect_grace)).await
+            {
+                return Err(Error::ConnectRejected);
+            }
+        }
+
+        Ok(())
     }
 
     /// Output some _arbitrary text_ to engine's log, this is
@@ -342,15 +1140,29 @@ where
     }
 
     /// Tell the engine we desire to stop handling messages.
+    ///
+    /// Once the engine acknowledged, no new messages are accepted and all the subscriptions are closed,
+    /// but the [`Self::messages`] streams still yield the messages the engine sent before acknowledging,
+    /// which must still be acknowledged, before ending.
     pub async fn quit(&self) -> Result<()> {
-        self.send(&Quit).await?;
-        self.subscribe::<QuitAck>(Topic::QuitAck)
-            .try_next()
-            .await?
-            .ok_or(Error::UnexpectedEof)?;
+        self.request::<QuitAck>(&Quit, Topic::QuitAck).await?;
 
+        // The messages read while waiting for the acknowledgement are staged for their streams,
+        // which drain them before ending
+        self.stop_messages();
         self.rx.unsubscribe_all();
 
         Ok(())
     }
+
+    /// Tell the engine we desire to stop handling messages like [`Self::quit`], yielding the messages
+    /// it sent before acknowledging, which must still be acknowledged.
+    ///
+    /// The stream ends once the engine acknowledged and the pending messages were yielded.
+    pub fn quit_and_drain(&self) -> impl TryStream<Ok = Request, Error = Error> {
+        let messages = self.messages().map_ok(Some);
+        let quit = futures::stream::once(self.quit()).map_ok(|()| None);
+
+        futures::stream::select(messages, quit).try_filter_map(future::ok)
+    }
 }
@@ -0,0 +1,64 @@
+use std::{
+    fs::{File, OpenOptions},
+    time::{Duration, Instant},
+};
+
+use futures::io::AllowStdIo;
+use futures_timer::Delay;
+
+use super::{Engine, Result};
+use crate::wire::ConnectRole;
+
+/// The error returned by Windows while all the instances of a named pipe are busy.
+const ERROR_PIPE_BUSY: i32 = 231;
+
+#[cfg_attr(docsrs, doc(cfg(windows)))]
+impl Engine<AllowStdIo<File>, AllowStdIo<File>> {
+    /// For how long opening a busy named pipe is retried in [`Self::connect_named_pipe`].
+    pub const PIPE_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Initialize a connection to the engine listening on the named pipe `name`,
+    /// either a bare name or a full `\\.\pipe\name` path, registering the module with a [`Self::connect`].
+    ///
+    /// Opening the pipe is retried while all of it's instances are busy, for at most [`Self::PIPE_BUSY_TIMEOUT`].
+    ///
+    /// Like with [`Engine::stdio`], the pipe is accessed with blocking I/O, the connect grace
+    /// of [`Self::with_connect_grace`] is then not waited for.
+    ///
+    /// As the pipe isn't opened for overlapped I/O, Windows serializes the accesses to it:
+    /// a write waits for a pending read to complete. Do not share the engine across tasks,
+    /// through [`Self::split`] or an [`EngineHandle`](super::EngineHandle), with this transport,
+    /// as a task waiting for a message would hold back the writes of all the others.
+    pub async fn connect_named_pipe(
+        name: &str,
+        role: ConnectRole,
+        channel: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<Self> {
+        let path = if name.starts_with(r"\\") {
+            name.to_string()
+        } else {
+            format!(r"\\.\pipe\{name}")
+        };
+
+        let deadline = Instant::now() + Self::PIPE_BUSY_TIMEOUT;
+        let pipe = loop {
+            match OpenOptions::new().read(true).write(true).open(&path) {
+                Err(err)
+                    if err.raw_os_error() == Some(ERROR_PIPE_BUSY) && Instant::now() < deadline =>
+                {
+                    tracing::debug!("named pipe `{path}` is busy, retrying");
+
+                    Delay::new(Duration::from_millis(50)).await;
+                }
+                pipe => break pipe?,
+            }
+        };
+
+        // The blocking reads of the pipe would hang the executor for the whole connect grace
+        let engine = Self::from_io(AllowStdIo::new(pipe.try_clone()?), AllowStdIo::new(pipe))
+            .with_connect_grace(Duration::ZERO);
+        engine.connect(role, channel).await?;
+
+        Ok(engine)
+    }
+}
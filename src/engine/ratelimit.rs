@@ -0,0 +1,93 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The verdict of a [`RateLimit::hit`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The event should be logged, along with the count of
+    /// `suppressed` events since the last logged one.
+    Log { suppressed: u64 },
+
+    /// The event should be suppressed.
+    Suppress,
+}
+
+struct State {
+    since: Option<Instant>,
+    logged: u64,
+    suppressed: u64,
+}
+
+/// A count-with-interval rate limiter, letting `burst` events
+/// through per `interval` and suppressing the others.
+pub struct RateLimit {
+    burst: u64,
+    interval: Duration,
+    state: Mutex<State>,
+}
+
+impl RateLimit {
+    pub fn new(burst: u64, interval: Duration) -> Self {
+        Self {
+            burst,
+            interval,
+            state: State {
+                since: None,
+                logged: 0,
+                suppressed: 0,
+            }
+            .into(),
+        }
+    }
+
+    pub fn hit(&self, now: Instant) -> Verdict {
+        let mut state = self.state.lock().unwrap();
+
+        if state
+            .since
+            .is_none_or(|since| now.saturating_duration_since(since) >= self.interval)
+        {
+            state.since = Some(now);
+            state.logged = 0;
+        }
+
+        if state.logged < self.burst {
+            state.logged += 1;
+
+            Verdict::Log {
+                suppressed: std::mem::take(&mut state.suppressed),
+            }
+        } else {
+            state.suppressed += 1;
+
+            Verdict::Suppress
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_limits_bursts() {
+        let limit = RateLimit::new(2, Duration::from_millis(50));
+        let start = Instant::now();
+
+        assert_eq!(limit.hit(start), Verdict::Log { suppressed: 0 });
+        assert_eq!(limit.hit(start), Verdict::Log { suppressed: 0 });
+        for _ in 0..10 {
+            assert_eq!(limit.hit(start), Verdict::Suppress);
+        }
+
+        let later = start + Duration::from_millis(40);
+        assert_eq!(limit.hit(later), Verdict::Suppress);
+
+        let later = start + Duration::from_millis(60);
+        assert_eq!(limit.hit(later), Verdict::Log { suppressed: 11 });
+        assert_eq!(limit.hit(later), Verdict::Log { suppressed: 0 });
+        assert_eq!(limit.hit(later), Verdict::Suppress);
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::BTreeSet,
+    io,
+    pin::pin,
+    sync::{self, Arc},
+    time::Duration,
+};
+
+use futures::{AsyncRead, AsyncWrite, TryStreamExt};
+use futures_timer::Delay;
+
+use super::{Engine, HandlerSet, Request, Result};
+use crate::wire::{ConnectRole, MessageName};
+
+/// The exponential backoff policy of a [`ReconnectingEngine`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl Backoff {
+    /// Wait for `initial` before the first re-dial attempt, doubling the delay
+    /// after each failed attempt, up to `max` between two attempts.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Apply the `multiplier` factor to the delay after each failed attempt, `2.0` by default.
+    ///
+    /// # Panics
+    ///
+    /// If the `multiplier` isn't a finite number greater than or equal to `1.0`.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        assert!(
+            multiplier.is_finite() && multiplier >= 1.0,
+            "the backoff multiplier must be finite and at least 1, got {multiplier}"
+        );
+
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// The successive delays between attempts, following this policy.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + use<> {
+        let Self {
+            initial,
+            max,
+            multiplier,
+        } = *self;
+
+        std::iter::successors(Some(initial.min(max)), move |delay| {
+            // Saturate to `max` when the next delay overflows
+            Some(
+                Duration::try_from_secs_f64(delay.as_secs_f64() * multiplier)
+                    .map_or(max, |delay| delay.min(max)),
+            )
+        })
+    }
+}
+
+/// The parameters required to (re-)establish a connection with the engine.
+struct Dialer<F> {
+    connector: F,
+    backoff: Backoff,
+    role: ConnectRole,
+    channel: Option<(String, Option<String>)>,
+}
+
+impl<F> Dialer<F> {
+    async fn dial<I, O, Fut>(
+        &self,
+        handlers: &HandlerSet,
+        watches: &BTreeSet<String>,
+    ) -> Engine<I, O>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+        F: Fn() -> Fut,
+        Fut: Future<Output = io::Result<(I, O)>>,
+    {
+        let mut delays = self.backoff.delays();
+
+        for attempt in 1.. {
+            tracing::info!(attempt, "dialing the engine");
+
+            match self.handshake(handlers, watches).await {
+                Ok(engine) => return engine,
+                Err(err) => tracing::warn!(attempt, "unable to connect to the engine: {err}"),
+            }
+
+            Delay::new(delays.next().expect("delays are infinite")).await;
+        }
+
+        unreachable!("attempts are infinite")
+    }
+
+    async fn handshake<I, O, Fut>(
+        &self,
+        handlers: &HandlerSet,
+        watches: &BTreeSet<String>,
+    ) -> Result<Engine<I, O>>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+        F: Fn() -> Fut,
+        Fut: Future<Output = io::Result<(I, O)>>,
+    {
+        let (rx, tx) = (self.connector)().await?;
+        let engine = Engine::from_io(rx, tx);
+
+        engine.connect(self.role, self.channel.clone()).await?;
+
+        // A refused handler is forgotten, while a refused watch is retried on the next re-dial
+        for (name, handler) in handlers {
+            if !engine
+                .install(handler.priority, name, handler.filter.clone())
+                .await?
+            {
+                tracing::warn!("the engine refused to re-install the handler for `{name}`");
+            }
+        }
+        for name in watches {
+            if !engine.watch(name).await? {
+                tracing::warn!("the engine refused to re-install the watch for `{name}`");
+            }
+        }
+
+        Ok(engine)
+    }
+}
+
+/// A _socket-based_ [`Engine`] connection, that transparently re-dials the engine
+/// when the connection is lost, performing the [`Engine::connect`] handshake
+/// and re-installing the previously installed handlers and watches.
+pub struct ReconnectingEngine<I, O, F>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    dialer: Dialer<F>,
+
+    engine: sync::Mutex<Arc<Engine<I, O>>>,
+    watches: sync::Mutex<BTreeSet<String>>,
+}
+
+impl<I, O, F, Fut> ReconnectingEngine<I, O, F>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+    F: Fn() -> Fut,
+    Fut: Future<Output = io::Result<(I, O)>>,
+{
+    /// Dial the engine with the `connector`, retrying following the `backoff` policy,
+    /// and register the connection with the provided `role` and `channel`.
+    pub async fn connect(
+        connector: F,
+        backoff: Backoff,
+        role: ConnectRole,
+        channel: impl Into<Option<(String, Option<String>)>>,
+    ) -> Self {
+        let dialer = Dialer {
+            connector,
+            backoff,
+            role,
+            channel: channel.into(),
+        };
+        let engine = dialer.dial(&Default::default(), &Default::default()).await;
+
+        Self {
+            dialer,
+
+            engine: sync::Mutex::new(Arc::new(engine)),
+            watches: Default::default(),
+        }
+    }
+
+    /// Re-dial the engine, replacing the current connection.
+    pub async fn reconnect(&self) {
+        let handlers = self.engine().handlers();
+        let watches = self.watches.lock().unwrap().clone();
+
+        let engine = self.dialer.dial(&handlers, &watches).await;
+        *self.engine.lock().unwrap() = Arc::new(engine);
+    }
+
+    /// The current connection to the engine.
+    pub fn engine(&self) -> Arc<Engine<I, O>> {
+        self.engine.lock().unwrap().clone()
+    }
+
+    /// Process the messages from the engine with the `handler`,
+    /// re-dialing the engine whenever the connection is lost.
+    ///
+    /// The `handler` is responsible for acknowledging the [`Request`] with the provided [`Engine`],
+    /// the engine being re-dialed as well when it fails with a [recoverable](super::Error::is_recoverable) error.
+    pub async fn serve<H, HFut>(&self, mut handler: H) -> Result<()>
+    where
+        H: FnMut(Arc<Engine<I, O>>, Request) -> HFut,
+        HFut: Future<Output = Result<()>>,
+    {
+        loop {
+            let engine = self.engine();
+
+            {
+                let mut messages = pin!(engine.messages());
+                loop {
+                    let result = match messages.try_next().await {
+                        Ok(Some(req)) => handler(engine.clone(), req).await,
+                        Ok(None) => break,
+                        Err(err) => Err(err),
+                    };
+
+                    match result {
+                        Ok(()) => (),
+                        Err(err) if err.is_recoverable() => break,
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+
+            tracing::warn!("lost the connection to the engine, reconnecting");
+            self.reconnect().await;
+        }
+    }
+
+    /// See [`Engine::install`], the handler is re-installed on reconnection.
+    pub async fn install(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<bool> {
+        self.engine().install(priority, name, filter).await
+    }
+
+    /// See [`Engine::uninstall`].
+    pub async fn uninstall(&self, name: impl Into<MessageName>) -> Result<bool> {
+        self.engine().uninstall(name).await
+    }
+
+    /// See [`Engine::watch`], the watch is re-installed on reconnection.
+    pub async fn watch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        let name = String::from(name.into());
+        let success = self.engine().watch(&name).await?;
+
+        if success {
+            self.watches.lock().unwrap().insert(name);
+        }
+
+        Ok(success)
+    }
+
+    /// See [`Engine::unwatch`].
+    pub async fn unwatch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        let name = String::from(name.into());
+        let success = self.engine().unwatch(&name).await?;
+
+        if success {
+            self.watches.lock().unwrap().remove(&name);
+        }
+
+        Ok(success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_backs_off_exponentially() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+
+        assert_eq!(
+            backoff.delays().take(5).collect::<Vec<_>>(),
+            [100, 200, 400, 500, 500].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn it_saturates_at_the_maximum() {
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::MAX).with_multiplier(1e300);
+
+        assert_eq!(
+            backoff.delays().take(3).collect::<Vec<_>>(),
+            [Duration::from_secs(1), Duration::MAX, Duration::MAX]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the backoff multiplier must be finite and at least 1")]
+    fn it_refuses_shrinking_multipliers() {
+        let _ = Backoff::default().with_multiplier(0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "the backoff multiplier must be finite and at least 1")]
+    fn it_refuses_nan_multipliers() {
+        let _ = Backoff::default().with_multiplier(f64::NAN);
+    }
+}
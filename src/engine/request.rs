@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use crate::wire::Message;
+use crate::wire::{Message, Params};
 
 #[cfg(doc)]
 use super::Engine;
@@ -10,16 +10,49 @@ use super::Engine;
 #[must_use = "messages must be ack'ed, even if not processed with Engine::ack"]
 pub struct Request {
     inner: Option<Message>,
+
+    /// The parameters as received, to only acknowledge the changed ones.
+    pub(super) original: Option<Params>,
 }
 
 impl Request {
-    pub(super) fn new(inner: Message) -> Self {
-        Self { inner: Some(inner) }
+    pub(crate) fn new(inner: Message) -> Self {
+        Self {
+            inner: Some(inner),
+            original: None,
+        }
+    }
+
+    /// Create a request keeping track of it's original parameters,
+    /// see [`Engine::with_delta_acks`].
+    pub(crate) fn tracked(inner: Message) -> Self {
+        Self {
+            original: Some(inner.kv.clone()),
+            inner: Some(inner),
+        }
     }
 
     pub(super) fn into_inner(mut self) -> Message {
         self.inner.take().expect("Req was already into_inner'ed")
     }
+
+    /// Set the parameter `key` to `value`, to be forwarded when ack'ing the message.
+    pub fn set_param(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.kv.insert(key.into(), value.into());
+    }
+
+    /// Remove the parameter `key`, returning it's value if it was present.
+    ///
+    /// Note that the engine keeps the parameters absent from the acknowledgement unchanged,
+    /// so the parameter is not removed from the engine's message.
+    pub fn remove_param(&mut self, key: &str) -> Option<String> {
+        self.kv.remove(key)
+    }
+
+    /// Set the textual return value, to be forwarded when ack'ing the message.
+    pub fn set_retvalue(&mut self, retvalue: impl Into<String>) {
+        self.retvalue = retvalue.into();
+    }
 }
 
 impl Deref for Request {
@@ -0,0 +1,38 @@
+use futures::{
+    StreamExt,
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    lock::Mutex,
+};
+
+/// A bounded pool of slots, limiting the count of concurrent operations.
+pub struct Slots {
+    tx: UnboundedSender<()>,
+    rx: Mutex<UnboundedReceiver<()>>,
+}
+
+impl Slots {
+    pub fn new(count: usize) -> Self {
+        let (tx, rx) = mpsc::unbounded();
+        for _ in 0..count {
+            tx.unbounded_send(()).expect("receiver is alive");
+        }
+
+        Self { tx, rx: rx.into() }
+    }
+
+    /// Wait for a slot to be available, releasing it once the [`Slot`] is dropped.
+    pub async fn acquire(&self) -> Slot<'_> {
+        self.rx.lock().await.next().await.expect("sender is alive");
+
+        Slot(&self.tx)
+    }
+}
+
+/// An acquired slot from [`Slots`].
+pub struct Slot<'s>(&'s UnboundedSender<()>);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.0.unbounded_send(()).ok();
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use futures::{AsyncRead, AsyncWrite, TryStream};
+
+use super::{Engine, Error, Request, Result};
+use crate::wire::{DebugLevel, InstallAck, MessageAck, MessageName, Params};
+
+/// The _reading_ half of an [`Engine`], created with [`Engine::split`].
+///
+/// This half receives messages and watches from the engine and acknowledges messages.
+pub struct EngineReader<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: Arc<Engine<I, O>>,
+}
+
+impl<I, O> EngineReader<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// See [`Engine::messages`].
+    pub fn messages(&self) -> impl TryStream<Ok = Request, Error = Error> {
+        self.engine.messages()
+    }
+
+    /// See [`Engine::messages_named`].
+    pub fn messages_named(
+        &self,
+        name: impl Into<MessageName>,
+    ) -> impl TryStream<Ok = Request, Error = Error> {
+        self.engine.messages_named(name)
+    }
+
+    /// See [`Engine::watches`].
+    pub fn watches(&self) -> impl TryStream<Ok = MessageAck, Error = Error> {
+        self.engine.watches()
+    }
+
+    /// See [`Engine::ack`].
+    pub async fn ack(&self, req: Request, processed: bool) -> Result<()> {
+        self.engine.ack(req, processed).await
+    }
+
+    /// See [`Engine::ack_with`].
+    pub async fn ack_with(
+        &self,
+        req: Request,
+        processed: bool,
+        retvalue: impl Into<String>,
+        kv: Params,
+    ) -> Result<()> {
+        self.engine.ack_with(req, processed, retvalue, kv).await
+    }
+}
+
+/// The _writing_ half of an [`Engine`], created with [`Engine::split`].
+///
+/// This half issues commands to the engine and waits for their acknowledgements.
+pub struct EngineWriter<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: Arc<Engine<I, O>>,
+}
+
+impl<I, O> EngineWriter<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// See [`Engine::install`].
+    pub async fn install(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<bool> {
+        self.engine.install(priority, name, filter).await
+    }
+
+    /// See [`Engine::install_full`].
+    pub async fn install_full(
+        &self,
+        priority: impl Into<Option<u64>>,
+        name: impl Into<MessageName>,
+        filter: impl Into<Option<(String, Option<String>)>>,
+    ) -> Result<InstallAck> {
+        self.engine.install_full(priority, name, filter).await
+    }
+
+    /// See [`Engine::uninstall`].
+    pub async fn uninstall(&self, name: impl Into<MessageName>) -> Result<bool> {
+        self.engine.uninstall(name).await
+    }
+
+    /// See [`Engine::watch`].
+    pub async fn watch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        self.engine.watch(name).await
+    }
+
+    /// See [`Engine::unwatch`].
+    pub async fn unwatch(&self, name: impl Into<MessageName>) -> Result<bool> {
+        self.engine.unwatch(name).await
+    }
+
+    /// See [`Engine::setlocal`].
+    pub async fn setlocal(
+        &self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<bool> {
+        self.engine.setlocal(name, value).await
+    }
+
+    /// See [`Engine::setlocal_full`].
+    pub async fn setlocal_full(
+        &self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(bool, String)> {
+        self.engine.setlocal_full(name, value).await
+    }
+
+    /// See [`Engine::getlocal`].
+    pub async fn getlocal(&self, name: impl Into<String>) -> Result<String> {
+        self.engine.getlocal(name).await
+    }
+
+    /// See [`Engine::message`].
+    pub async fn message(
+        &self,
+        name: impl Into<MessageName>,
+        retvalue: impl Into<String>,
+        kv: Params,
+    ) -> Result<(bool, String, Params)> {
+        self.engine.message(name, retvalue, kv).await
+    }
+
+    /// See [`Engine::output`].
+    pub async fn output(&self, text: impl Into<String>) -> Result<()> {
+        self.engine.output(text).await
+    }
+
+    /// See [`Engine::debug`].
+    pub async fn debug(&self, level: DebugLevel, text: impl Into<String>) -> Result<()> {
+        self.engine.debug(level, text).await
+    }
+
+    /// See [`Engine::raw_send`].
+    pub async fn raw_send(&self, line: impl AsRef<str>) -> Result<()> {
+        self.engine.raw_send(line).await
+    }
+
+    /// See [`Engine::quit`].
+    pub async fn quit(&self) -> Result<()> {
+        self.engine.quit().await
+    }
+}
+
+impl<I, O> Engine<I, O>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    /// Split the engine into independent [`EngineReader`] and [`EngineWriter`] halves,
+    /// to be moved to separate tasks.
+    ///
+    /// Both halves share the same underlying connection and subscriptions:
+    /// an acknowledgement read from the engine while the reader is polled is
+    /// routed to the writer waiting for it, and conversely.
+    ///
+    /// ## Cancellation
+    ///
+    /// Dropping a pending call on either half doesn't affect the other half:
+    /// - a call waiting for it's acknowledgement drops it's subscription,
+    ///   and the late acknowledgement is then discarded as unhandled,
+    /// - a dropped [`EngineReader::messages`] stream lets the messages received afterwards
+    ///   be acknowledged as not processed, so they are passed to the next handler,
+    /// - a call dropped while writing may leave a partial line on the wire,
+    ///   which the engine will answer with an `Error in` notification.
+    pub fn split(self) -> (EngineReader<I, O>, EngineWriter<I, O>) {
+        let engine = Arc::new(self);
+
+        (
+            EngineReader {
+                engine: engine.clone(),
+            },
+            EngineWriter { engine },
+        )
+    }
+}
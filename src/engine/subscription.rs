@@ -0,0 +1,139 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use facet::Facet;
+use futures::{AsyncRead, AsyncWrite, Stream, StreamExt, future::BoxFuture};
+
+use super::{Engine, Error, Result, Tee, Topic};
+use crate::{
+    subable::{Item, Subed},
+    wire::{self, ErrorIn},
+};
+
+/// A subscription to a [`Topic`] of the engine, yielding it's parsed items.
+pub struct Subscription<'e, I, O, T>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    engine: &'e Engine<I, O>,
+    queue: Subed<Tee<I>, Topic>,
+
+    /// Whether an `Error in` received on the topic is returned as [`Error::EngineRejected`].
+    rejectable: bool,
+
+    /// The pending default response to an unhandled item,
+    /// only allocated on this uncommon path.
+    unhandled: Option<BoxFuture<'e, Result<()>>>,
+
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<'e, I, O, T> Subscription<'e, I, O, T>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    pub fn new(engine: &'e Engine<I, O>, topic: Topic) -> Self {
+        let queue = engine.rx.subscribe(topic.clone());
+
+        Self::with_queue(engine, topic, queue)
+    }
+
+    /// Subscribe to the `topic` like [`Self::new`], only to the items
+    /// successfully parsed and satisfying the `predicate`.
+    pub fn filtered(
+        engine: &'e Engine<I, O>,
+        topic: Topic,
+        predicate: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self
+    where
+        T: for<'de> Facet<'de>,
+    {
+        let queue = engine
+            .rx
+            .subscribe_filter(topic.clone(), move |recvd: &String| {
+                wire::from_str(recvd).is_ok_and(|item| predicate(&item))
+            });
+
+        Self::with_queue(engine, topic, queue)
+    }
+
+    fn with_queue(engine: &'e Engine<I, O>, topic: Topic, queue: Subed<Tee<I>, Topic>) -> Self {
+        // Rejections of messages whose sender is gone fall back to the watches, ignore them there
+        let rejectable = topic != Topic::Watch;
+
+        Self {
+            engine,
+            queue,
+            rejectable,
+            unhandled: None,
+            _item: PhantomData,
+        }
+    }
+
+    fn respond(&mut self, recvd: String) {
+        let engine = self.engine;
+
+        self.unhandled = Some(Box::pin(
+            async move { engine.default_response(&recvd).await },
+        ));
+    }
+}
+
+impl<'e, I, O, T> Stream for Subscription<'e, I, O, T>
+where
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+    T: for<'de> Facet<'de>,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            if let Some(unhandled) = &mut this.unhandled {
+                let result = ready!(unhandled.as_mut().poll(cx));
+                this.unhandled = None;
+
+                if let Err(err) = result {
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+
+            let item = ready!(this.queue.poll_next_unpin(cx));
+            *this.engine.last_recv.lock().unwrap() = this.engine.timer.now();
+
+            if let Some(Ok(Item::Unhandled(recvd) | Item::Subscribed(recvd))) = &item {
+                this.engine.metrics.received(recvd);
+            }
+
+            match item {
+                None => return Poll::Ready(None),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+                Some(Ok(Item::Unhandled(recvd))) => this.respond(recvd),
+                Some(Ok(Item::Subscribed(recvd))) => {
+                    if let Some(ErrorIn { original }) = ErrorIn::parse(&recvd)
+                        && this.rejectable
+                    {
+                        return Poll::Ready(Some(Err(Error::EngineRejected(original))));
+                    }
+
+                    if let Ok(item) = wire::from_str(&recvd) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                    if ErrorIn::parse(&recvd).is_none() {
+                        this.engine.metrics.decode_error();
+                    }
+
+                    // The item was only classified from it's keyword, handle it as unhandled
+                    this.respond(recvd);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, ready},
+};
+
+use futures::{
+    AsyncBufReadExt, AsyncRead, Stream, StreamExt,
+    channel::mpsc,
+    io::{BufReader, Lines},
+};
+
+/// The observers of the lines read from the engine.
+#[derive(Default)]
+pub struct Taps {
+    senders: Mutex<Vec<mpsc::Sender<String>>>,
+    dropped: AtomicU64,
+}
+
+impl Taps {
+    /// Register a new observer, buffering up to `capacity` lines.
+    pub fn tap(&self, capacity: usize) -> mpsc::Receiver<String> {
+        let (tap, lines) = mpsc::channel(capacity);
+        self.senders.lock().unwrap().push(tap);
+
+        lines
+    }
+
+    /// The count of lines dropped for the observers not keeping up.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// The lines read from the engine, copied to the observers in [`Taps`] as they are read.
+pub struct Tee<I> {
+    lines: Lines<BufReader<I>>,
+    taps: Arc<Taps>,
+}
+
+impl<I: AsyncRead> Tee<I> {
+    pub fn new(rx: I, taps: Arc<Taps>) -> Self {
+        Self {
+            lines: BufReader::new(rx).lines(),
+            taps,
+        }
+    }
+}
+
+impl<I: AsyncRead + Unpin> Stream for Tee<I> {
+    type Item = io::Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.lines.poll_next_unpin(cx));
+        let mut senders = self.taps.senders.lock().unwrap();
+
+        match &item {
+            // Forget the observers that are gone, and drop the lines of the ones lagging behind
+            Some(Ok(line)) => senders.retain_mut(|tap| match tap.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(err) if err.is_full() => {
+                    self.taps.dropped.fetch_add(1, Ordering::Relaxed);
+
+                    true
+                }
+                Err(_) => false,
+            }),
+
+            // End the observers' streams along with the lines
+            None => senders.clear(),
+
+            Some(Err(_)) => (),
+        }
+
+        Poll::Ready(item)
+    }
+}
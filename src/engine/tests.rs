@@ -0,0 +1,2658 @@
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    pin::{Pin, pin},
+    sync::{Arc, Mutex, atomic::AtomicBool},
+    time::Duration,
+};
+
+use futures::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, FutureExt, StreamExt,
+    executor::block_on,
+    io::{BufReader, Lines},
+};
+
+use super::*;
+use crate::mock::{MockEngine, PipeReader, PipeWriter, SessionRecorder, pipe};
+
+/// An in-memory engine, with the `inject` end to send lines to
+/// the [`Engine`] and the `sent` end to read what the [`Engine`] sent.
+fn engine() -> (
+    Engine<PipeReader, PipeWriter>,
+    PipeWriter,
+    Lines<BufReader<PipeReader>>,
+) {
+    let (rx, inject) = pipe();
+    let (sent, tx) = pipe();
+
+    (
+        Engine::from_io(rx, tx),
+        inject,
+        BufReader::new(sent).lines(),
+    )
+}
+
+/// Drive the time of the `engine` with a [`MockTimer`].
+fn mock_timer(engine: &mut Engine<PipeReader, PipeWriter>) -> timer::MockTimer {
+    let timer = timer::MockTimer::new();
+
+    *engine.last_recv.get_mut().unwrap() = timer.now();
+    engine.timer = Box::new(timer.clone());
+
+    timer
+}
+
+#[test]
+fn watchdog_detects_dead_link() {
+    let (mut engine, _inject, mut sent) = engine();
+    let timer = mock_timer(&mut engine);
+    let engine = engine.with_watchdog(Duration::from_millis(50));
+
+    block_on(async {
+        timer.advance(Duration::from_millis(60));
+
+        let mut output = pin!(engine.output("are you there ?"));
+        assert!(output.as_mut().now_or_never().is_none());
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>setlocal:engine.runid:"
+        );
+
+        timer.advance(Duration::from_millis(50));
+        assert!(matches!(
+            output.await.map_err(|err| err.to_string()),
+            Err(err) if err == "`output` command failed: the engine didn't answer the watchdog probe, link is dead"
+        ));
+    });
+}
+
+#[test]
+fn watchdog_passes_live_link() {
+    let (mut engine, mut inject, mut sent) = engine();
+    let timer = mock_timer(&mut engine);
+    let engine = engine.with_watchdog(Duration::from_millis(50));
+
+    block_on(async {
+        timer.advance(Duration::from_millis(60));
+        inject
+            .write_all(b"%%<setlocal:engine.runid:1234:true\n")
+            .await
+            .unwrap();
+
+        engine.output("are you there ?").await.unwrap();
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>setlocal:engine.runid:"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>output:are you there ?"
+        );
+    });
+}
+
+#[test]
+fn keepalives_are_ignored() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        inject.write_all(b"\n  \n%%<quit\n").await.unwrap();
+        engine.quit().await.unwrap();
+        assert_eq!(engine.keepalives(), 2);
+        drop(engine);
+
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert!(sent.try_next().await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn keepalive_keywords_are_ignored() {
+    let (engine, mut inject, mut sent) = engine();
+    let engine = engine.with_keepalive_keyword("%%>keepalive");
+
+    block_on(async {
+        inject
+            .write_all(b"%%>keepalive\n%%>keepalive:42\n%%>bogus\n%%<quit\n")
+            .await
+            .unwrap();
+        engine.quit().await.unwrap();
+        assert_eq!(engine.keepalives(), 2);
+        assert_eq!(engine.dropped(), 1);
+        drop(engine);
+
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert!(sent.try_next().await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn reconfigure_only_changes_difference() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<install:100:a:true\n%%<install:100:b:true\n%%<install:100:c:true\n")
+            .await
+            .unwrap();
+        assert!(engine.install(None, "a", None).await.unwrap());
+        assert!(engine.install(None, "b", None).await.unwrap());
+        assert!(engine.install(None, "c", None).await.unwrap());
+
+        inject
+            .write_all(b"%%<uninstall:100:a:true\n%%<uninstall:100:c:true\n%%<install:50:c:true\n%%<install:100:d:true\n")
+            .await
+            .unwrap();
+        let report = engine
+            .reconfigure(HandlerSet::from([
+                ("b".into(), Handler::default()),
+                (
+                    "c".into(),
+                    Handler {
+                        priority: Some(50),
+                        filter: None,
+                    },
+                ),
+                ("d".into(), Handler::default()),
+            ]))
+            .await
+            .unwrap();
+
+        assert_eq!(report.uninstalled, ["a"]);
+        assert_eq!(report.reinstalled, ["c"]);
+        assert_eq!(report.installed, ["d"]);
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            engine.handlers().into_keys().collect::<Vec<_>>(),
+            ["b", "c", "d"]
+        );
+
+        drop(engine);
+        let sent = sent.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(
+            sent[3..],
+            [
+                "%%>uninstall:a",
+                "%%>uninstall:c",
+                "%%>install:50:c",
+                "%%>install::d"
+            ]
+        );
+    });
+}
+
+#[test]
+fn await_ready_after_delay() {
+    let (mut engine, mut inject, mut sent) = engine();
+    let timer = mock_timer(&mut engine);
+
+    block_on(async {
+        futures::join!(
+            async {
+                engine.await_ready(Duration::from_secs(1)).await.unwrap();
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>setlocal:engine.runid:"
+                );
+
+                timer.advance(Duration::from_millis(100));
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>setlocal:engine.runid:"
+                );
+
+                inject
+                    .write_all(b"%%<setlocal:engine.runid:1234:true\n")
+                    .await
+                    .unwrap();
+            }
+        );
+    });
+}
+
+#[test]
+fn await_ready_times_out() {
+    let (mut engine, _inject, mut sent) = engine();
+    let timer = mock_timer(&mut engine);
+
+    block_on(async {
+        futures::join!(
+            async {
+                assert!(matches!(
+                    engine.await_ready(Duration::from_millis(150)).await,
+                    Err(Error::Timeout)
+                ));
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>setlocal:engine.runid:"
+                );
+
+                timer.advance(Duration::from_millis(100));
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>setlocal:engine.runid:"
+                );
+
+                timer.advance(Duration::from_millis(50));
+            }
+        );
+    });
+}
+
+#[test]
+fn one_shot_subscriptions_unsubscribe() {
+    let (engine, mut inject, _sent) = engine();
+    let topic = Topic::SetLocalAck("engine.runid".into());
+
+    block_on(async {
+        let ack = engine.subscribe_once::<SetLocalAck>(topic.clone());
+        assert!(engine.rx.is_subscribed(&topic));
+
+        inject
+            .write_all(b"%%<setlocal:engine.runid:1234:true\n%%<setlocal:engine.runid:5678:true\n")
+            .await
+            .unwrap();
+
+        assert_eq!(ack.await.unwrap().value, "1234");
+        assert!(!engine.rx.is_subscribed(&topic));
+    });
+}
+
+#[test]
+fn requests_time_out() {
+    let (mut engine, _inject, mut sent) = engine();
+    let timer = mock_timer(&mut engine);
+    let engine = engine.with_timeout(Duration::from_millis(50));
+
+    block_on(async {
+        let mut watch = pin!(engine.watch("call.route"));
+        assert!(watch.as_mut().now_or_never().is_none());
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>watch:call.route"
+        );
+
+        timer.advance(Duration::from_millis(40));
+        assert!(watch.as_mut().now_or_never().is_none());
+
+        timer.advance(Duration::from_millis(10));
+        assert!(matches!(
+            watch.await,
+            Err(Error::Request { command: "watch", source }) if matches!(*source, Error::Timeout)
+        ));
+    });
+}
+
+#[test]
+fn max_pending_acks_queues_calls() {
+    let (mut engine, mut inject, mut sent) = engine();
+    engine.set_max_pending_acks(1);
+
+    block_on(async {
+        futures::join!(
+            async {
+                assert!(engine.install(None, "a", None).await.unwrap());
+            },
+            async {
+                assert!(engine.install(None, "b", None).await.unwrap());
+            },
+            async {
+                // Both calls were polled before this one, so `b` is waiting for the slot
+                assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>install::a");
+                assert!(sent.try_next().now_or_never().is_none());
+
+                inject.write_all(b"%%<install:100:a:true\n").await.unwrap();
+                assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>install::b");
+
+                inject.write_all(b"%%<install:100:b:true\n").await.unwrap();
+            }
+        );
+    });
+}
+
+#[test]
+fn dropped_lines_are_counted() {
+    /// A subscriber counting the warnings about dropped lines.
+    #[derive(Default)]
+    struct Warnings(Arc<Mutex<usize>>);
+
+    impl tracing::Subscriber for Warnings {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message(String);
+
+            impl tracing::field::Visit for Message {
+                fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+                    if field.name() == "message" {
+                        self.0 = format!("{value:?}");
+                    }
+                }
+            }
+
+            let mut message = Message(String::new());
+            event.record(&mut message);
+
+            if *event.metadata().level() == tracing::Level::WARN
+                && message.0.starts_with("unhandled message, dropped")
+            {
+                *self.0.lock().unwrap() += 1;
+            }
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    let (engine, mut inject, _sent) = engine();
+    let warnings = Warnings::default();
+    let count = warnings.0.clone();
+
+    tracing::subscriber::with_default(warnings, || {
+        block_on(async {
+            for _ in 0..20 {
+                inject.write_all(b"%%<unknown:line\n").await.unwrap();
+            }
+            inject.write_all(b"%%<quit\n").await.unwrap();
+
+            engine.quit().await.unwrap();
+        })
+    });
+
+    // Every line is counted, while only the first ones of the burst are logged
+    assert_eq!(engine.dropped(), 20);
+    assert_eq!(*count.lock().unwrap(), 10);
+}
+
+#[test]
+fn split_routes_acks() {
+    let (engine, mut inject, mut sent) = engine();
+    let (reader, writer) = engine.split();
+
+    block_on(async {
+        futures::join!(
+            async {
+                let (processed, retvalue, _) = writer
+                    .message("call.route", "", Default::default())
+                    .await
+                    .unwrap();
+
+                assert!(processed);
+                assert_eq!(retvalue, "sip/1234");
+            },
+            async {
+                let req = std::pin::pin!(reader.messages())
+                    .try_next()
+                    .await
+                    .unwrap()
+                    .unwrap();
+
+                assert_eq!(req.name, "engine.timer");
+                reader.ack(req, false).await.unwrap();
+            },
+            async {
+                inject
+                    .write_all(b"%%>message:234479208:1095112795:engine.timer::time=1095112795\n")
+                    .await
+                    .unwrap();
+
+                let line = sent.try_next().await.unwrap().unwrap();
+                let message = wire::from_str::<Message>(&line).unwrap();
+                inject
+                    .write_all(
+                        format!("%%<message:{}:true:call.route:sip/1234\n", message.id).as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%<message:234479208:false:engine.timer::time=1095112795"
+                );
+            }
+        );
+    });
+}
+
+#[test]
+fn typed_setlocal() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<setlocal:timeout:5000:true\n%%<setlocal:timebomb:true:true\n")
+            .await
+            .unwrap();
+
+        assert!(engine.set_timeout(Duration::from_secs(5)).await.unwrap());
+        assert!(engine.set_timebomb(true).await.unwrap());
+        assert!(matches!(
+            engine.setlocal("timeout", "abc").await,
+            Err(Error::InvalidParamValue { .. })
+        ));
+        assert!(matches!(
+            engine.setlocal("restart", "maybe").await,
+            Err(Error::InvalidParamValue { .. })
+        ));
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%>setlocal:timeout:5000", "%%>setlocal:timebomb:true"]
+        );
+    });
+}
+
+#[test]
+fn typed_local_helpers() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%<setlocal:timeout:10000:true\n\
+                  %%<setlocal:trackparam:router:true\n\
+                  %%<setlocal:disconnected:false:true\n\
+                  %%<setlocal:reenter:true:true\n\
+                  %%<setlocal:bufsize:16384:true\n",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(engine.get_timeout().await.unwrap(), Duration::from_secs(10));
+        assert!(engine.set_trackparam("router").await.unwrap());
+        assert!(engine.set_disconnected(false).await.unwrap());
+        assert!(engine.set_reenter(true).await.unwrap());
+        assert!(engine.set_bufsize(16384).await.unwrap());
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%>setlocal:timeout:",
+                "%%>setlocal:trackparam:router",
+                "%%>setlocal:disconnected:false",
+                "%%>setlocal:reenter:true",
+                "%%>setlocal:bufsize:16384"
+            ]
+        );
+    });
+}
+
+#[test]
+fn setlocal_returns_applied_value() {
+    let (engine, mut inject, _sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<setlocal:bufsize:1024:true\n")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            engine.setlocal_full("bufsize", "512").await.unwrap(),
+            (true, "1024".into())
+        );
+    });
+}
+
+#[test]
+fn install_returns_assigned_priority() {
+    let (engine, mut inject, _sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<install:75:engine.timer:true\n")
+            .await
+            .unwrap();
+
+        let ack = engine
+            .install_full(None, "engine.timer", None)
+            .await
+            .unwrap();
+        assert!(ack.success);
+        assert_eq!(ack.priority, 75);
+    });
+}
+
+#[test]
+fn mutated_request_is_acked() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%>message:234479208:1095112795:call.route::called=1234:caller=5678\n")
+            .await
+            .unwrap();
+
+        let mut req = std::pin::pin!(engine.messages())
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        req.set_param("called", "4321");
+        req.set_param("trackparam", "yengine");
+        assert_eq!(req.remove_param("caller").as_deref(), Some("5678"));
+        assert_eq!(req.remove_param("caller"), None);
+        req.set_retvalue("sip/4321");
+        engine.ack(req, true).await.unwrap();
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%<message:234479208:true:call.route:sip/4321:called=4321:trackparam=yengine"]
+        );
+    });
+}
+
+#[test]
+fn delta_acks_only_send_changes() {
+    let (engine, mut inject, sent) = engine();
+    let engine = engine.with_delta_acks();
+
+    block_on(async {
+        inject
+            .write_all(b"%%>message:234479208:1095112795:call.route::called=1234:caller=5678:x=y\n")
+            .await
+            .unwrap();
+
+        let mut req = std::pin::pin!(engine.messages())
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        req.set_param("called", "4321");
+        req.set_param("caller", "5678");
+        req.set_param("trackparam", "yengine");
+        req.remove_param("x");
+        engine.ack(req, true).await.unwrap();
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%<message:234479208:true:call.route::called=4321:trackparam=yengine"]
+        );
+    });
+}
+
+#[test]
+fn ack_with_overrides() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%>message:234479208:1095112795:call.route:old:called=1234\n")
+            .await
+            .unwrap();
+
+        let req = std::pin::pin!(engine.messages())
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        engine
+            .ack_with(
+                req,
+                true,
+                "sip/1234",
+                Params::from([("called".into(), "4321".into())]),
+            )
+            .await
+            .unwrap();
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%<message:234479208:true:call.route:sip/1234:called=4321"]
+        );
+    });
+}
+
+#[test]
+fn install_filters() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<install:100:a:true\n%%<install:100:a:true\n%%<install:100:b:true\n%%<install:100:b:true\n")
+            .await
+            .unwrap();
+
+        assert!(
+            engine
+                .install(None, "a", Filter::eq("called", "12345"))
+                .await
+                .unwrap()
+        );
+        assert!(
+            engine
+                .install(None, "a", Some(("called".into(), Some("12345".into()))))
+                .await
+                .unwrap()
+        );
+        assert!(
+            engine
+                .install(None, "b", Filter::on("called"))
+                .await
+                .unwrap()
+        );
+        assert!(
+            engine
+                .install(None, "b", Some(("called".into(), None)))
+                .await
+                .unwrap()
+        );
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%>install::a:called:12345",
+                "%%>install::a:called:12345",
+                "%%>install::b:called",
+                "%%>install::b:called"
+            ]
+        );
+    });
+}
+
+#[test]
+fn reconnects_after_drop() {
+    let (rx1, mut inject1) = pipe();
+    let (sent1, tx1) = pipe();
+    let (rx2, mut inject2) = pipe();
+    let (sent2, tx2) = pipe();
+
+    let conns = Mutex::new(VecDeque::from([(rx1, tx1), (rx2, tx2)]));
+    let connector = || {
+        let conn = conns.lock().unwrap().pop_front();
+        async move { conn.ok_or_else(|| io::ErrorKind::ConnectionRefused.into()) }
+    };
+    let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50));
+
+    block_on(async {
+        inject1.write_all(b"%%<install:100:a:true\n").await.unwrap();
+        inject2
+            .write_all(b"%%<install:100:a:true\n%%>message:234479208:1095112795:call.route:\n")
+            .await
+            .unwrap();
+
+        let engine =
+            ReconnectingEngine::connect(connector, backoff, ConnectRole::Global, None).await;
+        assert!(engine.install(None, "a", None).await.unwrap());
+        drop(inject1);
+
+        let serve = engine.serve(async |engine, req| engine.ack(req, true).await);
+        let check = async {
+            let sent = BufReader::new(sent2).lines();
+
+            assert_eq!(
+                sent.take(3).try_collect::<Vec<_>>().await.unwrap(),
+                [
+                    "%%>connect:global",
+                    "%%>install::a",
+                    "%%<message:234479208:true:call.route:"
+                ]
+            );
+        };
+
+        match future::select(pin!(serve), pin!(check)).await {
+            Either::Left((result, _)) => panic!("serving ended early: {result:?}"),
+            Either::Right(((), _)) => (),
+        }
+    });
+
+    let sent1 = block_on(BufReader::new(sent1).lines().try_collect::<Vec<_>>()).unwrap();
+    assert_eq!(sent1, ["%%>connect:global", "%%>install::a"]);
+}
+
+#[test]
+fn reconnects_after_handler_errors() {
+    let (rx1, mut inject1) = pipe();
+    let (_sent1, tx1) = pipe();
+    let (rx2, mut inject2) = pipe();
+    let (sent2, tx2) = pipe();
+
+    let conns = Mutex::new(VecDeque::from([(rx1, tx1), (rx2, tx2)]));
+    let connector = || {
+        let conn = conns.lock().unwrap().pop_front();
+        async move { conn.ok_or_else(|| io::ErrorKind::ConnectionRefused.into()) }
+    };
+    let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50));
+
+    block_on(async {
+        inject1.write_all(b"%%<install:100:a:true\n").await.unwrap();
+        inject2
+            .write_all(b"%%<install:100:a:false\n%%>message:2:1095112795:call.route:\n")
+            .await
+            .unwrap();
+
+        let engine =
+            ReconnectingEngine::connect(connector, backoff, ConnectRole::Global, None).await;
+        assert!(engine.install(None, "a", None).await.unwrap());
+        inject1
+            .write_all(b"%%>message:1:1095112795:call.route:\n")
+            .await
+            .unwrap();
+
+        // The handler's acknowledgement fails as the link drops mid-message
+        let serve = engine.serve(async |engine, req| {
+            if req.id == "1" {
+                return Err(Error::UnexpectedEof);
+            }
+
+            engine.ack(req, true).await
+        });
+        let check = async {
+            let sent = BufReader::new(sent2).lines();
+
+            assert_eq!(
+                sent.take(3).try_collect::<Vec<_>>().await.unwrap(),
+                [
+                    "%%>connect:global",
+                    "%%>install::a",
+                    "%%<message:2:true:call.route:"
+                ]
+            );
+        };
+
+        match future::select(pin!(serve), pin!(check)).await {
+            Either::Left((result, _)) => panic!("serving ended early: {result:?}"),
+            Either::Right(((), _)) => (),
+        }
+
+        // The handler refused on re-dial is forgotten
+        assert!(engine.engine().handlers().is_empty());
+    });
+}
+
+#[test]
+fn split_halves_run_on_separate_threads() {
+    let (engine, mut inject, mut sent) = engine();
+    let (reader, writer) = engine.split();
+
+    // Subscribe beforehand, so the message can't be received before the reader is running
+    let messages = reader.messages();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            block_on(async {
+                let req = pin!(messages).try_next().await.unwrap().unwrap();
+                reader.ack(req, true).await.unwrap();
+            })
+        });
+        scope.spawn(|| {
+            block_on(async {
+                assert!(writer.watch("engine.timer").await.unwrap());
+            })
+        });
+
+        block_on(async {
+            assert_eq!(
+                sent.try_next().await.unwrap().unwrap(),
+                "%%>watch:engine.timer"
+            );
+
+            inject
+                .write_all(
+                    b"%%>message:234479208:1095112795:engine.timer:\n%%<watch:engine.timer:true\n",
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(
+                sent.try_next().await.unwrap().unwrap(),
+                "%%<message:234479208:true:engine.timer:"
+            );
+        });
+    });
+}
+
+#[test]
+fn handle_clones_route_acks() {
+    let (engine, mut inject, mut sent) = engine();
+    let handle = engine.into_handle();
+    let (first, second) = (handle.clone(), handle.clone());
+
+    block_on(async {
+        futures::join!(
+            async {
+                let (processed, retvalue, _) = first
+                    .message("call.route", "", Default::default())
+                    .await
+                    .unwrap();
+
+                assert!(processed);
+                assert_eq!(retvalue, "first");
+            },
+            async {
+                let (processed, retvalue, _) = second
+                    .message("call.route", "", Default::default())
+                    .await
+                    .unwrap();
+
+                assert!(!processed);
+                assert_eq!(retvalue, "second");
+            },
+            async {
+                let first =
+                    wire::from_str::<Message>(&sent.try_next().await.unwrap().unwrap()).unwrap();
+                let second =
+                    wire::from_str::<Message>(&sent.try_next().await.unwrap().unwrap()).unwrap();
+                assert_ne!(first.id, second.id);
+
+                // Acknowledge in reverse order, to check the routing
+                inject
+                    .write_all(
+                        format!(
+                            "%%<message:{}:false:call.route:second\n%%<message:{}:true:call.route:first\n",
+                            second.id, first.id
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        );
+    });
+}
+
+#[test]
+fn heartbeat_acks_timers() {
+    let (engine, mut inject, mut sent) = engine();
+    let mut ticks = 0;
+
+    block_on(async {
+        futures::join!(
+            async {
+                assert!(engine.heartbeat(None, |_| ticks += 1).await.unwrap());
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>install::engine.timer"
+                );
+                inject
+                    .write_all(b"%%<install:100:engine.timer:true\n")
+                    .await
+                    .unwrap();
+
+                for time in 1095112795..1095112798 {
+                    inject
+                        .write_all(
+                            format!("%%>message:{time}:{time}:engine.timer::time={time}\n")
+                                .as_bytes(),
+                        )
+                        .await
+                        .unwrap();
+
+                    assert_eq!(
+                        sent.try_next().await.unwrap().unwrap(),
+                        format!("%%<message:{time}:false:engine.timer::time={time}")
+                    );
+                }
+
+                inject.shut();
+            }
+        );
+    });
+
+    assert_eq!(ticks, 3);
+}
+
+#[test]
+fn concurrent_installs_of_same_name() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        futures::join!(
+            async {
+                assert!(engine.install(100, "engine.timer", None).await.unwrap());
+            },
+            async {
+                assert!(!engine.install(50, "engine.timer", None).await.unwrap());
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>install:100:engine.timer"
+                );
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>install:50:engine.timer"
+                );
+
+                inject
+                    .write_all(
+                        b"%%<install:100:engine.timer:true\n%%<install:50:engine.timer:false\n",
+                    )
+                    .await
+                    .unwrap();
+            }
+        );
+    });
+
+    assert_eq!(engine.handlers()["engine.timer"].priority, Some(100));
+}
+
+#[test]
+fn rejected_request_errors() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        futures::join!(
+            async {
+                assert!(matches!(
+                    engine.install(100, "engine.timer", None).await.as_ref().map_err(Error::root),
+                    Err(Error::EngineRejected(original)) if original == "%%>install:100:engine.timer"
+                ));
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>install:100:engine.timer"
+                );
+
+                inject
+                    .write_all(b"Error in:%%>install:100:engine.timer\n")
+                    .await
+                    .unwrap();
+            }
+        );
+    });
+
+    assert!(engine.handlers().is_empty());
+}
+
+#[test]
+fn free_text_is_escaped() {
+    let (engine, _inject, mut sent) = engine();
+
+    block_on(async {
+        engine.output("line1\nline2").await.unwrap();
+        engine
+            .debug(DebugLevel::Warn, "line1\r\nline2")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>output:line1%Jline2"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>debug:5:line1%M%Jline2"
+        );
+    });
+}
+
+#[test]
+fn quit_drains_pending_messages() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=alice\n\
+                  %%>message:2:1095112795:call.route::caller=bob\n\
+                  %%<quit\n",
+            )
+            .await
+            .unwrap();
+
+        let drained = engine
+            .quit_and_drain()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            drained
+                .iter()
+                .map(|req| req.id.as_str())
+                .collect::<Vec<_>>(),
+            ["1", "2"]
+        );
+
+        for req in drained {
+            engine.ack(req, true).await.unwrap();
+        }
+
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:true:call.route::caller=alice"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:2:true:call.route::caller=bob"
+        );
+    });
+}
+
+#[test]
+fn quit_lets_messages_drain() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        let mut messages = pin!(engine.messages());
+
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=alice\n\
+                  %%>message:2:1095112795:call.route::caller=bob\n\
+                  %%<quit\n\
+                  %%>message:3:1095112795:call.route::caller=carol\n",
+            )
+            .await
+            .unwrap();
+
+        engine.quit().await.unwrap();
+
+        for id in ["1", "2"] {
+            let req = messages.try_next().await.unwrap().unwrap();
+            assert_eq!(req.id, id);
+
+            engine.ack(req, true).await.unwrap();
+        }
+        assert!(messages.try_next().await.unwrap().is_none());
+
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:true:call.route::caller=alice"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:2:true:call.route::caller=bob"
+        );
+    });
+}
+
+#[test]
+fn matching_messages_are_routed() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        let mut alice = pin!(engine.messages_matching(|message| {
+            message
+                .get("caller")
+                .is_some_and(|caller| caller == "alice")
+        }));
+        let mut others = pin!(engine.messages());
+
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:call.route::caller=alice\n",
+            )
+            .await
+            .unwrap();
+
+        let req = alice.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "2");
+        engine.ack(req, true).await.unwrap();
+
+        let req = others.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "1");
+        engine.ack(req, false).await.unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:2:true:call.route::caller=alice"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:false:call.route::caller=bob"
+        );
+    });
+}
+
+#[test]
+fn repeated_keys_are_acked() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        let mut messages = engine.messages();
+        inject
+            .write_all(b"%%>message:1:1095112795:call.route::a=1:a=2\n")
+            .await
+            .unwrap();
+
+        let req = messages.try_next().await.unwrap().unwrap();
+        assert_eq!(req.kv["a"], "2");
+        engine.ack(req, true).await.unwrap();
+        drop(messages);
+
+        // Without a subscriber, the message is acknowledged by default rather than dropped
+        inject
+            .write_all(b"%%>message:2:1095112795:call.route::a=1:a=2\n%%<quit\n")
+            .await
+            .unwrap();
+        engine.quit().await.unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:true:call.route::a=2"
+        );
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:2:false:::a=2"
+        );
+        assert_eq!(engine.dropped(), 0);
+    });
+}
+
+#[test]
+fn busy_messages_stream_keeps_its_messages() {
+    let (engine, mut inject, mut sent) = engine();
+    let engine = engine.with_id_source(|| "out.1".into());
+    let pending = crate::subable::STAGING_CAPACITY;
+
+    block_on(async {
+        let mut messages = engine.messages();
+        inject
+            .write_all(b"%%>message:0:1095112795:call.route::caller=bob\n")
+            .await
+            .unwrap();
+        let req = messages.try_next().await.unwrap().unwrap();
+
+        // The messages received while the stream awaits a request are kept for it
+        for id in 1..=pending {
+            inject
+                .write_all(
+                    format!("%%>message:{id}:1095112795:call.route::caller=bob\n").as_bytes(),
+                )
+                .await
+                .unwrap();
+        }
+        inject
+            .write_all(b"%%<message:out.1:true:call.cdr:\n")
+            .await
+            .unwrap();
+
+        assert!(
+            engine
+                .message("call.cdr", "", Params::new())
+                .await
+                .unwrap()
+                .0
+        );
+        engine.ack(req, true).await.unwrap();
+
+        for id in 1..=pending {
+            let req = messages.try_next().await.unwrap().unwrap();
+            assert_eq!(req.id, id.to_string());
+        }
+        assert_eq!(engine.dropped(), 0);
+
+        assert!(
+            sent.try_next()
+                .await
+                .unwrap()
+                .unwrap()
+                .starts_with("%%>message:out.1:")
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:0:true:call.route::caller=bob"
+        );
+        assert!(sent.try_next().now_or_never().is_none());
+    });
+}
+
+#[test]
+fn messages_hold_requests_back_once_staged() {
+    let (engine, mut inject, _sent) = engine();
+    let pending = crate::subable::STAGING_CAPACITY + 1;
+
+    block_on(async {
+        let mut messages = engine.messages();
+
+        for id in 0..pending {
+            inject
+                .write_all(
+                    format!("%%>message:{id}:1095112795:call.route::caller=bob\n").as_bytes(),
+                )
+                .await
+                .unwrap();
+        }
+        inject
+            .write_all(b"%%<setlocal:bufsize:64:true\n")
+            .await
+            .unwrap();
+
+        // The stream's staging is full, the request waits for it to be polled
+        let mut setlocal = pin!(engine.setlocal("bufsize", "64"));
+        assert!(setlocal.as_mut().now_or_never().is_none());
+
+        assert_eq!(messages.try_next().await.unwrap().unwrap().id, "0");
+        assert!(setlocal.await.unwrap());
+
+        for id in 1..pending {
+            assert_eq!(
+                messages.try_next().await.unwrap().unwrap().id,
+                id.to_string()
+            );
+        }
+    });
+}
+
+#[test]
+fn oldest_messages_stream_wins() {
+    let (engine, mut inject, _sent) = engine();
+
+    block_on(async {
+        let mut first = engine.messages();
+        let mut second = engine.messages();
+
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=alice\n\
+                  %%>message:2:1095112795:call.route::caller=bob\n",
+            )
+            .await
+            .unwrap();
+
+        // Polled concurrently, the streams don't share the messages
+        let (req, idle) = match future::select(first.try_next(), second.try_next()).await {
+            Either::Left((req, idle)) => (req, idle),
+            Either::Right(_) => panic!("the second stream received a message"),
+        };
+        assert_eq!(req.unwrap().unwrap().id, "1");
+        drop(idle);
+
+        let req = first.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "2");
+
+        // The second stream receives the messages once the first is dropped
+        drop(first);
+        inject
+            .write_all(b"%%>message:3:1095112795:call.route::caller=carol\n")
+            .await
+            .unwrap();
+
+        let req = second.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "3");
+    });
+}
+
+#[test]
+fn module_runs_messages() {
+    struct Router;
+
+    impl Module for Router {
+        type Error = Error;
+
+        async fn install<I, O>(&self, engine: &Engine<I, O>) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            engine.install(None, "call.route", None).await.map(drop)
+        }
+
+        async fn on_message<I, O>(&self, _engine: &Engine<I, O>, req: &mut Request) -> Result<bool>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            req.set_retvalue("sip/alice");
+
+            Ok(req.get("caller") == Some("bob"))
+        }
+    }
+
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        futures::join!(
+            async {
+                Router.run(&engine).await.unwrap();
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>install::call.route"
+                );
+                inject
+                    .write_all(
+                        b"%%<install:100:call.route:true\n\
+                          %%>message:1:1095112795:call.route::caller=bob\n\
+                          %%>message:2:1095112795:call.route::caller=eve\n",
+                    )
+                    .await
+                    .unwrap();
+
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%<message:1:true:call.route:sip/alice:caller=bob"
+                );
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%<message:2:false:call.route:sip/alice:caller=eve"
+                );
+
+                inject.shut();
+            }
+        );
+    });
+}
+
+#[test]
+fn module_survives_panics() {
+    struct Panicky;
+
+    impl Module for Panicky {
+        type Error = Error;
+
+        async fn install<I, O>(&self, _engine: &Engine<I, O>) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            Ok(())
+        }
+
+        async fn on_message<I, O>(&self, _engine: &Engine<I, O>, req: &mut Request) -> Result<bool>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            if req.id == "1" {
+                panic!("unexpected message");
+            }
+
+            Ok(true)
+        }
+    }
+
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:call.route::caller=eve\n",
+            )
+            .await
+            .unwrap();
+        inject.shut();
+
+        // Attaching the module runs it the same way as `Module::run`
+        engine.attach(Panicky).await.unwrap();
+
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%<message:1:false:call.route::caller=bob",
+                "%%<message:2:true:call.route::caller=eve",
+            ]
+        );
+    });
+}
+
+#[test]
+fn module_runs_concurrently() {
+    #[derive(Default)]
+    struct Slow(AtomicUsize);
+
+    impl Module for Slow {
+        type Error = Error;
+
+        const CONCURRENCY: usize = 2;
+
+        async fn install<I, O>(&self, _engine: &Engine<I, O>) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            Ok(())
+        }
+
+        async fn on_message<I, O>(&self, _engine: &Engine<I, O>, _req: &mut Request) -> Result<bool>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            self.0.fetch_add(1, Ordering::Relaxed);
+
+            // Wait for both messages to be processing at once
+            future::poll_fn(|cx| {
+                if self.0.load(Ordering::Relaxed) < 2 {
+                    cx.waker().wake_by_ref();
+                    return std::task::Poll::Pending;
+                }
+
+                std::task::Poll::Ready(())
+            })
+            .await;
+
+            Ok(true)
+        }
+    }
+
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:call.route::caller=eve\n",
+            )
+            .await
+            .unwrap();
+        inject.shut();
+
+        Slow::default().run(&engine).await.unwrap();
+
+        drop(engine);
+        let mut acks = sent.try_collect::<Vec<_>>().await.unwrap();
+        acks.sort();
+        assert_eq!(
+            acks,
+            [
+                "%%<message:1:true:call.route::caller=bob",
+                "%%<message:2:true:call.route::caller=eve",
+            ]
+        );
+    });
+}
+
+#[test]
+fn module_acks_running_messages_on_error() {
+    #[derive(Default)]
+    struct Failing(AtomicUsize);
+
+    impl Module for Failing {
+        type Error = Error;
+
+        const CONCURRENCY: usize = 2;
+
+        async fn install<I, O>(&self, _engine: &Engine<I, O>) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            Ok(())
+        }
+
+        async fn on_message<I, O>(&self, _engine: &Engine<I, O>, req: &mut Request) -> Result<bool>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            self.0.fetch_add(1, Ordering::Relaxed);
+
+            // Fail the first message while the second one is still running
+            let target = if req.id == "1" { 2 } else { 3 };
+            future::poll_fn(|cx| {
+                if self.0.load(Ordering::Relaxed) < target {
+                    cx.waker().wake_by_ref();
+                    return std::task::Poll::Pending;
+                }
+
+                std::task::Poll::Ready(())
+            })
+            .await;
+
+            if req.id == "1" {
+                self.0.fetch_add(1, Ordering::Relaxed);
+
+                return Err(Error::Timeout);
+            }
+
+            Ok(true)
+        }
+    }
+
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:call.route::caller=eve\n",
+            )
+            .await
+            .unwrap();
+        inject.shut();
+
+        assert!(matches!(
+            Failing::default().run(&engine).await,
+            Err(Error::Timeout)
+        ));
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%<message:1:false:call.route::caller=bob",
+                "%%<message:2:true:call.route::caller=eve",
+            ]
+        );
+    });
+}
+
+#[test]
+fn module_runs_watches() {
+    #[derive(Default)]
+    struct Watcher(Mutex<Vec<String>>);
+
+    impl Module for Watcher {
+        type Error = Error;
+
+        async fn install<I, O>(&self, engine: &Engine<I, O>) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            engine.watch("call.answered").await.map(drop)
+        }
+
+        async fn on_watch<I, O>(&self, _engine: &Engine<I, O>, watch: MessageAck) -> Result<()>
+        where
+            I: AsyncRead + Send + Unpin,
+            O: AsyncWrite + Send + Unpin,
+        {
+            self.0.lock().unwrap().push(watch.id);
+
+            Ok(())
+        }
+    }
+
+    let (engine, mut inject, mut sent) = engine();
+    let watcher = Watcher::default();
+
+    block_on(async {
+        futures::join!(
+            async {
+                watcher.run(&engine).await.unwrap();
+            },
+            async {
+                assert_eq!(
+                    sent.try_next().await.unwrap().unwrap(),
+                    "%%>watch:call.answered"
+                );
+                inject
+                    .write_all(
+                        b"%%<watch:call.answered:true\n\
+                          %%<message:234479208:true:call.answered::caller=bob\n",
+                    )
+                    .await
+                    .unwrap();
+
+                inject.shut();
+            }
+        );
+    });
+
+    assert_eq!(*watcher.0.lock().unwrap(), ["234479208"]);
+}
+
+#[test]
+fn failed_writes_keep_the_queued_uninstalls() {
+    /// A writer failing while `failing` is set.
+    struct Flaky(Arc<AtomicBool>, PipeWriter);
+
+    impl AsyncWrite for Flaky {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<io::Result<usize>> {
+            if self.0.load(Ordering::Relaxed) {
+                return std::task::Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+
+            Pin::new(&mut self.1).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            Pin::new(&mut self.1).poll_flush(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            Pin::new(&mut self.1).poll_close(cx)
+        }
+    }
+
+    let (rx, mut inject) = pipe();
+    let (sent, tx) = pipe();
+    let failing = Arc::new(AtomicBool::new(false));
+    let engine = Engine::from_io(rx, Flaky(failing.clone(), tx));
+    let mut sent = BufReader::new(sent).lines();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<install:100:call.route:true\n")
+            .await
+            .unwrap();
+
+        let guard = engine
+            .install_guarded(None, "call.route", None)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(guard);
+
+        failing.store(true, Ordering::Relaxed);
+        assert!(matches!(engine.flush_uninstalls().await, Err(Error::Io(_))));
+
+        failing.store(false, Ordering::Relaxed);
+        engine.flush_uninstalls().await.unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>install::call.route"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>uninstall:call.route"
+        );
+        assert!(engine.uninstalls.lock().unwrap().queued.is_empty());
+    });
+}
+
+#[test]
+fn dropped_guard_uninstalls() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<install:100:call.route:true\n")
+            .await
+            .unwrap();
+
+        let guard = engine
+            .install_guarded(None, "call.route", None)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(guard);
+        engine.flush_uninstalls().await.unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>install::call.route"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>uninstall:call.route"
+        );
+
+        // The handler is only forgotten once the engine acknowledged it's removal
+        assert!(engine.handlers().contains_key("call.route"));
+
+        inject
+            .write_all(b"%%<uninstall:100:call.route:true\n%%<install:100:call.execute:true\n%%<uninstall:100:call.execute:true\n")
+            .await
+            .unwrap();
+
+        let guard = engine
+            .install_guarded(None, "call.execute", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(guard.release().await.unwrap());
+
+        assert!(engine.handlers().is_empty());
+        assert_eq!(engine.dropped(), 0);
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>install::call.execute"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>uninstall:call.execute"
+        );
+    });
+}
+
+#[test]
+fn subscriptions_are_unpin_and_send() {
+    fn assert_unpin_send<T: Unpin + Send>(_: T) {}
+
+    let (engine, _inject, _sent) = engine();
+
+    assert_unpin_send(engine.messages());
+    assert_unpin_send(engine.watches());
+}
+
+#[test]
+fn messages_can_be_stopped() {
+    let (engine, mut inject, mut sent) = engine();
+
+    block_on(async {
+        let mut messages = pin!(engine.messages());
+        let mut named = pin!(engine.messages_named("call.route"));
+        let mut cdrs = pin!(engine.messages_named("call.cdr"));
+        assert!(engine.is_receiving_messages());
+        assert_eq!(engine.active_subscriptions(), 3);
+
+        engine.stop_messages();
+        assert!(messages.try_next().await.unwrap().is_none());
+        assert!(named.try_next().await.unwrap().is_none());
+        assert!(cdrs.try_next().await.unwrap().is_none());
+        assert!(!engine.is_receiving_messages());
+        assert_eq!(engine.active_subscriptions(), 0);
+
+        inject
+            .write_all(b"%%>message:1:1095112795:call.route::caller=bob\n%%<quit\n")
+            .await
+            .unwrap();
+        engine.quit().await.unwrap();
+
+        assert_eq!(sent.try_next().await.unwrap().unwrap(), "%%>quit");
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:false:::caller=bob"
+        );
+    });
+}
+
+#[test]
+fn ids_are_unique() {
+    let (engine, _inject, _sent) = engine();
+
+    let ids = (0..10_000)
+        .map(|_| engine.id())
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(ids.len(), 10_000);
+    assert!(ids.iter().all(|id| id.is_ascii()));
+}
+
+#[test]
+fn ids_are_generated_by_the_source() {
+    let (engine, mut inject, sent) = engine();
+    let mut seq = 0;
+    let mut engine = engine.with_id_source(move || {
+        seq += 1;
+        format!("test.{seq}")
+    });
+    engine.clock = || SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795);
+
+    block_on(async {
+        for (id, caller) in [("test.1", "bob"), ("test.2", "eve")] {
+            let kv = Params::from([("caller".into(), caller.into())]);
+            let ack = format!("%%<message:{id}:true:call.route:\n");
+
+            let (result, _) = futures::join!(
+                engine.message("call.route", "", kv),
+                inject.write_all(ack.as_bytes())
+            );
+            assert!(result.unwrap().0);
+        }
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%>message:test.1:1095112795:call.route::caller=bob",
+                "%%>message:test.2:1095112795:call.route::caller=eve"
+            ]
+        );
+    });
+}
+
+#[test]
+fn pings_measure_latency() {
+    let (mut engine, mut inject, _sent) = engine();
+    let timer = mock_timer(&mut engine);
+
+    block_on(async {
+        let mut ping = pin!(engine.ping(Duration::from_secs(5)));
+        assert!(ping.as_mut().now_or_never().is_none());
+
+        timer.advance(Duration::from_millis(3));
+        inject
+            .write_all(b"%%<setlocal:engine.runid:1095112795:true\n")
+            .await
+            .unwrap();
+        assert_eq!(ping.await.unwrap(), Duration::from_millis(3));
+
+        let mut ping = pin!(engine.ping(Duration::from_millis(50)));
+        assert!(ping.as_mut().now_or_never().is_none());
+
+        timer.advance(Duration::from_millis(50));
+        assert!(matches!(ping.await, Err(Error::Timeout)));
+    });
+}
+
+#[test]
+fn engine_info_is_cached() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        let (info, _) = futures::join!(
+            engine.engine_info(),
+            inject.write_all(
+                b"%%<setlocal:engine.version:6.4.0:true\n\
+                %%<setlocal:engine.runid:1095112700:true\n\
+                %%<setlocal:engine.nodename:pbx1:true\n\
+                %%<setlocal:engine.clientmode:false:true\n\
+                %%<setlocal:engine.maxworkers:10:true\n"
+            )
+        );
+        let expected = EngineInfo {
+            version: "6.4.0".into(),
+            runid: 1095112700,
+            nodename: "pbx1".into(),
+            clientmode: false,
+            maxworkers: 10,
+        };
+        assert_eq!(info.unwrap(), expected);
+
+        // The second call is answered from the cache
+        assert_eq!(engine.engine_info().await.unwrap(), expected);
+
+        drop(engine);
+        assert_eq!(sent.try_collect::<Vec<_>>().await.unwrap().len(), 5);
+    });
+}
+
+#[test]
+fn metrics_reflect_the_traffic() {
+    let (engine, mut inject, sent) = engine();
+    let engine = engine.with_id_source(|| "out.1".into());
+
+    let received = [
+        "%%>message:in.1:1095112795:call.route::caller=bob",
+        "%%<message:out.1:bogus:call.cdr:",
+        "%%<message:out.1:true:call.cdr:",
+    ];
+    // Account for the line terminators
+    let bytes = |lines: &[&str]| lines.iter().map(|line| line.len() as u64 + 1).sum();
+
+    block_on(async {
+        inject
+            .write_all(format!("{}\n", received[0]).as_bytes())
+            .await
+            .unwrap();
+        let req = engine.messages().try_next().await.unwrap().unwrap();
+        engine.ack(req, true).await.unwrap();
+
+        let acks = format!("{}\n{}\n", received[1], received[2]);
+        let (result, _) = futures::join!(
+            engine.message("call.cdr", "", Params::new()),
+            inject.write_all(acks.as_bytes())
+        );
+        assert!(result.unwrap().0);
+
+        let metrics = engine.metrics();
+        drop(engine);
+        let sent = sent.try_collect::<Vec<_>>().await.unwrap();
+
+        assert_eq!(
+            metrics,
+            EngineMetrics {
+                messages_sent: 1,
+                messages_received: 1,
+                acks_sent: 1,
+                bytes_in: bytes(&received),
+                bytes_out: bytes(&sent.iter().map(String::as_str).collect::<Vec<_>>()),
+                decode_errors: 1,
+            }
+        );
+        assert_eq!(sent.len(), 2);
+    });
+}
+
+#[test]
+fn message_times_are_monotonic() {
+    // A clock going back one second on every reading
+    fn regressing() -> SystemTime {
+        static SECS: AtomicU64 = AtomicU64::new(1095112795);
+
+        SystemTime::UNIX_EPOCH + Duration::from_secs(SECS.fetch_sub(1, Ordering::Relaxed))
+    }
+
+    let (mut engine, _inject, _sent) = engine();
+    engine.clock = regressing;
+
+    let times = (0..3).map(|_| engine.now()).collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795); 3]
+    );
+}
+
+#[test]
+fn crlf_lines_are_trimmed() {
+    let (engine, mut inject, _sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(b"%%<watch:call.answered:true\r\n%%<quit\r\n")
+            .await
+            .unwrap();
+
+        assert!(engine.watch("call.answered").await.unwrap());
+        engine.quit().await.unwrap();
+        assert_eq!(engine.dropped(), 0);
+    });
+}
+
+#[test]
+fn unhandled_lines_reach_callback() {
+    let unhandled = Arc::new(Mutex::new(Vec::new()));
+
+    let (engine, mut inject, _sent) = engine();
+    let engine = engine.on_unhandled({
+        let unhandled = unhandled.clone();
+        move |line| unhandled.lock().unwrap().push(line.to_owned())
+    });
+
+    block_on(async {
+        inject.write_all(b"%%<bogus:line\n%%<quit\n").await.unwrap();
+        engine.quit().await.unwrap();
+    });
+
+    assert_eq!(engine.dropped(), 1);
+    assert_eq!(*unhandled.lock().unwrap(), ["%%<bogus:line"]);
+}
+
+#[test]
+fn raw_lines_are_sent_verbatim() {
+    let (rx, _inject) = pipe();
+    let (mut sent, tx) = pipe();
+    let engine = Engine::from_io(rx, tx);
+
+    block_on(async {
+        engine.raw_send("%%>newcommand:arg%z:").await.unwrap();
+
+        let mut buf = [0; 64];
+        let len = sent.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"%%>newcommand:arg%z:\n");
+    });
+}
+
+#[test]
+fn raw_lines_tee_routed_lines() {
+    let (engine, mut inject, mut sent) = engine();
+    let raw = engine.raw_lines();
+
+    block_on(async {
+        inject
+            .write_all(b"%%>message:1:1095112795:call.route::caller=bob\n%%<bogus\n")
+            .await
+            .unwrap();
+        inject.shut();
+
+        let mut messages = pin!(engine.messages());
+        let req = messages.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "1");
+        engine.ack(req, true).await.unwrap();
+        assert!(messages.try_next().await.unwrap().is_none());
+
+        assert_eq!(
+            raw.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%>message:1:1095112795:call.route::caller=bob", "%%<bogus"]
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%<message:1:true:call.route::caller=bob"
+        );
+    });
+}
+
+#[test]
+fn raw_lines_are_dropped_when_lagging() {
+    let (engine, mut inject, _sent) = engine();
+    let raw = engine.raw_lines();
+
+    let lines = Engine::<PipeReader, PipeWriter>::RAW_LINES_CAPACITY * 2;
+    block_on(async {
+        inject
+            .write_all(format!("{}%%<quit\n", "\n".repeat(lines)).as_bytes())
+            .await
+            .unwrap();
+        engine.quit().await.unwrap();
+
+        let dropped = engine.raw_lines_dropped();
+        assert!(dropped > 0);
+        drop(engine);
+
+        let received = raw.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(received.len() as u64 + dropped, lines as u64 + 1);
+    });
+}
+
+#[test]
+fn batches_are_flushed_once() {
+    let (rx, mut inject) = pipe();
+    let (sent, tx) = pipe();
+    let state = sent.0.clone();
+    let engine = Engine::from_io(rx, tx);
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%<install:100:call.route:true\n\
+                  %%<install:100:call.execute:false\n\
+                  %%<install:100:chan.hangup:true\n",
+            )
+            .await
+            .unwrap();
+
+        let installs = ["call.route", "call.execute", "chan.hangup"].map(|name| Install {
+            priority: None,
+            name: name.into(),
+            filter: None,
+        });
+        let acks = engine.install_batch(installs).await.unwrap();
+        assert_eq!(state.lock().unwrap().flushes, 1);
+
+        // The acknowledgements are collected, rather than discarded as unhandled
+        assert_eq!(
+            acks.iter().map(|ack| ack.success).collect::<Vec<_>>(),
+            [true, false, true]
+        );
+        assert_eq!(engine.dropped(), 0);
+        assert_eq!(
+            engine.handlers().keys().collect::<Vec<_>>(),
+            ["call.route", "chan.hangup"]
+        );
+
+        let mut sent = BufReader::new(sent).lines();
+        for name in ["call.route", "call.execute", "chan.hangup"] {
+            assert_eq!(
+                sent.try_next().await.unwrap().unwrap(),
+                format!("%%>install::{name}")
+            );
+        }
+    });
+}
+
+#[test]
+fn batches_are_written_vectored() {
+    let (rx, _inject) = pipe();
+    let (mut sent, tx) = pipe();
+    let state = sent.0.clone();
+    let engine = Engine::from_io(rx, tx);
+
+    block_on(async {
+        let outputs = (0..100)
+            .map(|n| Output {
+                text: format!("line {n}"),
+            })
+            .collect::<Vec<_>>();
+        engine.send_batch(&outputs).await.unwrap();
+
+        // The whole batch is written at once, rather than line by line
+        assert_eq!(state.lock().unwrap().writes, 1);
+
+        drop(engine);
+        let mut received = String::new();
+        sent.read_to_string(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            (0..100)
+                .map(|n| format!("%%>output:line {n}\n"))
+                .collect::<String>()
+        );
+    });
+}
+
+#[test]
+fn unserializable_messages_are_refused() {
+    #[derive(facet::Facet)]
+    #[facet(type_tag = "%%>pair")]
+    struct Pair {
+        values: [u8; 2],
+    }
+
+    impl wire::Unacknowledged for Pair {}
+
+    let (engine, _inject, sent) = engine();
+
+    block_on(async {
+        assert!(matches!(
+            engine.send_batch(&[Pair { values: [1, 2] }]).await,
+            Err(Error::Format(wire::Error::UnsupportedType(_)))
+        ));
+        engine.output("still alive").await.unwrap();
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            ["%%>output:still alive"]
+        );
+    });
+}
+
+#[test]
+#[should_panic(expected = "the engine's line buffer can't be empty")]
+fn empty_bufsize_is_refused() {
+    let (engine, _inject, _sent) = engine();
+    let _ = engine.with_bufsize(0);
+}
+
+#[test]
+fn long_lines_are_refused() {
+    let (engine, mut inject, mut sent) = engine();
+    let engine = engine.with_bufsize(32);
+
+    block_on(async {
+        assert!(matches!(
+            engine
+                .output("a rather long line, past the buffer")
+                .await
+                .as_ref()
+                .map_err(Error::root),
+            Err(Error::LineTooLong { len: 45, max: 32 })
+        ));
+
+        inject
+            .write_all(b"%%<setlocal:bufsize:64:true\n")
+            .await
+            .unwrap();
+        assert!(engine.setlocal("bufsize", "64").await.unwrap());
+        engine
+            .output("a rather long line, past the buffer")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>setlocal:bufsize:64"
+        );
+        assert_eq!(
+            sent.try_next().await.unwrap().unwrap(),
+            "%%>output:a rather long line, past the buffer"
+        );
+    });
+}
+
+#[test]
+fn named_messages_are_routed() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:chan.hangup::caller=bob\n\
+                  %%>message:3:1095112795:call.execute::caller=bob\n\
+                  %%>message:4:1095112795:call.route::caller=eve\n",
+            )
+            .await
+            .unwrap();
+        inject.shut();
+
+        let (routes, executes) = futures::join!(
+            engine
+                .messages_named("call.route")
+                .and_then(async |req| Ok((req.id.clone(), engine.ack(req, true).await?)))
+                .try_collect::<Vec<_>>(),
+            engine
+                .messages_named("call.execute")
+                .and_then(async |req| Ok((req.id.clone(), engine.ack(req, true).await?)))
+                .try_collect::<Vec<_>>(),
+        );
+
+        assert_eq!(
+            routes.unwrap(),
+            [("1".into(), ()), ("4".into(), ())] as [(String, ()); 2]
+        );
+        assert_eq!(executes.unwrap(), [("3".into(), ())] as [(String, ()); 1]);
+
+        drop(engine);
+        let mut acks = sent.try_collect::<Vec<_>>().await.unwrap();
+        acks.sort();
+        assert_eq!(
+            acks,
+            [
+                "%%<message:1:true:call.route::caller=bob",
+                "%%<message:2:false:::caller=bob",
+                "%%<message:3:true:call.execute::caller=bob",
+                "%%<message:4:true:call.route::caller=eve",
+            ]
+        );
+    });
+}
+
+#[test]
+fn handled_messages_are_acked_once() {
+    let (engine, mut inject, sent) = engine();
+
+    block_on(async {
+        inject
+            .write_all(
+                b"%%>message:1:1095112795:call.route::caller=bob\n\
+                  %%>message:2:1095112795:call.route::caller=eve\n\
+                  %%>message:3:1095112795:call.route::caller=joe\n\
+                  %%>message:4:1095112795:call.route::caller=ann\n",
+            )
+            .await
+            .unwrap();
+        inject.shut();
+
+        let mut handled = Vec::new();
+        let result = engine
+            .handle("call.route", async |req: &mut Request| {
+                handled.push(req.id.clone());
+
+                match req.id.as_str() {
+                    "1" => Ok(true),
+                    "2" => Ok(false),
+                    _ => Err(Error::Timeout),
+                }
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(handled, ["1", "2", "3"]);
+
+        drop(engine);
+        assert_eq!(
+            sent.try_collect::<Vec<_>>().await.unwrap(),
+            [
+                "%%<message:1:true:call.route::caller=bob",
+                "%%<message:2:false:call.route::caller=eve",
+                "%%<message:3:false:call.route::caller=joe",
+            ]
+        );
+    });
+}
+
+#[test]
+fn mock_engine_round_trips() {
+    let (engine, mut mock) = MockEngine::new();
+
+    block_on(async {
+        mock.inject("%%<install:100:call.route:true").await;
+        assert!(engine.install(100, "call.route", None).await.unwrap());
+        mock.expect_sent("%%>install:100:call.route").await;
+
+        mock.inject("%%>message:1:1095112795:call.route::caller=bob")
+            .await;
+        mock.shut();
+
+        engine
+            .handle("call.route", async |req: &mut Request| {
+                req.set_retvalue("sip/bob");
+
+                Ok::<_, Error>(true)
+            })
+            .await
+            .unwrap();
+        mock.expect_sent("%%<message:1:true:call.route:sip/bob:caller=bob")
+            .await;
+
+        drop(engine);
+        assert_eq!(mock.next_sent().await, None);
+    });
+}
+
+#[test]
+fn sessions_are_replayed() {
+    async fn route(engine: &Engine<impl AsyncRead + Send + Unpin, impl AsyncWrite + Send + Unpin>) {
+        assert!(engine.install(100, "call.route", None).await.unwrap());
+        engine
+            .handle("call.route", async |req: &mut Request| {
+                req.set_retvalue("sip/bob");
+
+                Ok::<_, Error>(true)
+            })
+            .await
+            .unwrap();
+    }
+
+    let (rx, mut inject) = pipe();
+    let (sent, tx) = pipe();
+    let recorder = SessionRecorder::new(Vec::new());
+    recorder.set_clock(|| SystemTime::UNIX_EPOCH + Duration::from_micros(1_095_112_795_000_042));
+    let (rx, tx) = recorder.record(rx, tx);
+    let engine = Engine::from_io(rx, tx);
+
+    block_on(async {
+        let mut sent = BufReader::new(sent).lines();
+
+        futures::join!(route(&engine), async {
+            assert_eq!(
+                sent.try_next().await.unwrap().unwrap(),
+                "%%>install:100:call.route"
+            );
+            inject
+                .write_all(
+                    b"%%<install:100:call.route:true\n\
+                      %%>message:1:1095112795:call.route::caller=bob\n",
+                )
+                .await
+                .unwrap();
+            inject.shut();
+        });
+    });
+    drop(engine);
+
+    let session = String::from_utf8(recorder.into_writer().unwrap()).unwrap();
+    assert_eq!(
+        session,
+        "1095112795.000042 > %%>install:100:call.route\n\
+         1095112795.000042 < %%<install:100:call.route:true\n\
+         1095112795.000042 < %%>message:1:1095112795:call.route::caller=bob\n\
+         1095112795.000042 > %%<message:1:true:call.route:sip/bob:caller=bob\n"
+    );
+
+    let (engine, mut mock) = MockEngine::new();
+    block_on(async {
+        let (_, replayed) = futures::join!(route(&engine), mock.replay(session.as_bytes()));
+        replayed.unwrap();
+    });
+
+    // The timestamps may be omitted
+    let (engine, mut mock) = MockEngine::new();
+    block_on(async {
+        let bare = session.replace("1095112795.000042 ", "");
+        let (_, replayed) = futures::join!(route(&engine), mock.replay(bare.as_bytes()));
+        replayed.unwrap();
+    });
+
+    let (engine, mut mock) = MockEngine::new();
+    block_on(async {
+        let diverging = session.replace("sip/bob", "sip/eve");
+        let (_, replayed) = futures::join!(route(&engine), mock.replay(diverging.as_bytes()));
+        assert_eq!(replayed.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    });
+}
+
+#[cfg(feature = "tracing-subscriber")]
+#[test]
+fn tracing_events_reach_engine_log() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let (engine, mut mock) = MockEngine::new();
+    let (layer, forward) = EngineLayer::new(engine.into_handle());
+
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+        tracing::info!(target: "module", caller = "bob", "routing call");
+        tracing::debug!(target: "module", "looking up");
+        tracing::trace!(target: "yengine::engine", "ignored");
+    });
+
+    block_on(async {
+        forward.await.unwrap();
+
+        mock.expect_sent("%%>output:routing call caller=bob").await;
+        mock.expect_sent("%%>debug:9:looking up").await;
+        assert_eq!(mock.next_sent().await, None);
+    });
+}
+
+#[test]
+fn connect_roles_are_checked() {
+    let (engine, mut mock) = MockEngine::new();
+    let engine = engine.with_connect_grace(Duration::ZERO);
+
+    block_on(async {
+        engine.connect(ConnectRole::Global, None).await.unwrap();
+        engine
+            .connect(ConnectRole::Play, ("ourstream/1".to_owned(), None))
+            .await
+            .unwrap();
+        engine
+            .connect(
+                ConnectRole::PlayRec,
+                ("ourstream/1".to_owned(), Some("other".to_owned())),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            engine
+                .connect(ConnectRole::Global, ("ourstream/1".to_owned(), None))
+                .await,
+            Err(Error::InvalidConnectRole(ConnectRole::Global))
+        ));
+        assert!(matches!(
+            engine.connect(ConnectRole::Record, None).await,
+            Err(Error::InvalidConnectRole(ConnectRole::Record))
+        ));
+
+        drop(engine);
+        mock.expect_sent("%%>connect:global").await;
+        mock.expect_sent("%%>connect:play:ourstream/1").await;
+        mock.expect_sent("%%>connect:playrec:ourstream/1:other")
+            .await;
+        assert_eq!(mock.next_sent().await, None);
+    });
+}
+
+#[test]
+fn connect_waits_for_the_grace() {
+    let (mut engine, mut mock) = MockEngine::new();
+    let timer = mock_timer(&mut engine);
+
+    block_on(async {
+        let mut connect = pin!(engine.connect(ConnectRole::Global, None));
+        assert!(connect.as_mut().now_or_never().is_none());
+
+        timer.advance(Engine::<PipeReader, PipeWriter>::DEFAULT_CONNECT_GRACE);
+        connect.await.unwrap();
+        mock.expect_sent("%%>connect:global").await;
+    });
+}
+
+#[test]
+fn rejected_connect_is_reported() {
+    let (engine, mut mock) = MockEngine::new();
+    let engine = engine.with_connect_grace(Duration::from_secs(5));
+
+    block_on(async {
+        mock.shut();
+
+        assert!(matches!(
+            engine.connect(ConnectRole::Global, None).await,
+            Err(Error::ConnectRejected)
+        ));
+        mock.expect_sent("%%>connect:global").await;
+    });
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_round_trip() {
+    use crate::blocking::BlockingEngine;
+
+    let (engine, mut mock) = MockEngine::new();
+    let engine = BlockingEngine::new(engine);
+
+    block_on(async {
+        mock.inject("%%<install:100:call.route:true").await;
+        mock.inject("%%>message:1:1095112795:call.route::caller=bob")
+            .await;
+        mock.shut();
+    });
+
+    assert!(engine.install(100, "call.route", None).unwrap());
+
+    let mut req = engine.next_message().unwrap().unwrap();
+    req.set_retvalue("sip/bob");
+    engine.ack(req, true).unwrap();
+    assert!(engine.next_message().unwrap().is_none());
+
+    drop(engine);
+    block_on(async {
+        mock.expect_sent("%%>install:100:call.route").await;
+        mock.expect_sent("%%<message:1:true:call.route:sip/bob:caller=bob")
+            .await;
+    });
+}
+
+#[test]
+fn errors_are_classified() {
+    let recoverable = [
+        Error::Io(io::Error::other("reset")),
+        Error::UnexpectedEof,
+        Error::LinkDead,
+        Error::Timeout,
+        Error::Request {
+            command: "install",
+            source: Box::new(Error::Timeout),
+        },
+    ];
+    for err in recoverable {
+        assert!(err.is_recoverable(), "for {err:?}");
+    }
+
+    let fatal = [
+        Error::Format(wire::Error::EmptyInput),
+        Error::EngineRejected("%%>bogus".into()),
+        Error::LineTooLong { len: 45, max: 32 },
+        Error::ConnectRejected,
+        Error::InvalidConnectRole(ConnectRole::Global),
+        Error::InvalidParamValue {
+            name: "engine.version".into(),
+            value: "?".into(),
+        },
+        Error::HandlerPanicked {
+            id: "1".into(),
+            panic: "oops".into(),
+        },
+        Error::Request {
+            command: "install",
+            source: Box::new(Error::EngineRejected("%%>install".into())),
+        },
+    ];
+    for err in fatal {
+        assert!(!err.is_recoverable(), "for {err:?}");
+    }
+}
+
+#[test]
+fn idless_messages_are_not_acked() {
+    let (engine, mut mock) = MockEngine::new();
+
+    block_on(async {
+        mock.inject("%%>message::1095112795:engine.timer:").await;
+        mock.inject("%%>message:1:1095112795:engine.timer:").await;
+        mock.shut();
+
+        let mut messages = engine.messages();
+        let req = messages.try_next().await.unwrap().unwrap();
+        assert_eq!((req.id.as_str(), req.name.as_str()), ("", "engine.timer"));
+        engine.ack(req, true).await.unwrap();
+
+        let req = messages.try_next().await.unwrap().unwrap();
+        assert_eq!(req.id, "1");
+        engine.ack(req, true).await.unwrap();
+
+        drop(messages);
+        drop(engine);
+        mock.expect_sent("%%<message:1:true:engine.timer:").await;
+        assert_eq!(mock.next_sent().await, None);
+    });
+}
+
+#[test]
+fn transcripts_are_replayed_from_files() {
+    let dir = std::env::temp_dir().join(format!("yengine-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input, output) = (dir.join("input"), dir.join("output"));
+
+    std::fs::write(
+        &input,
+        "%%>message:1:1095112795:call.route::caller=bob\n%%>message:2:1095112795:call.route::caller=eve\n",
+    )
+    .unwrap();
+
+    block_on(async {
+        let engine = Engine::from_files(&input, &output).unwrap();
+
+        let mut messages = engine.messages();
+        while let Some(mut req) = messages.try_next().await.unwrap() {
+            let caller = req.get("caller").unwrap().to_string();
+            req.set_retvalue(format!("sip/{caller}"));
+
+            engine.ack(req, true).await.unwrap();
+        }
+    });
+
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "%%<message:1:true:call.route:sip/bob:caller=bob\n%%<message:2:true:call.route:sip/eve:caller=eve\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(windows)]
+#[test]
+fn missing_named_pipes_are_reported() {
+    block_on(async {
+        let name = format!("yengine-test-{}", std::process::id());
+
+        assert!(matches!(
+            Engine::connect_named_pipe(&name, ConnectRole::Global, None).await,
+            Err(Error::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+    });
+}
+
+#[cfg(windows)]
+#[test]
+fn named_pipes_round_trip() {
+    let name = format!("yengine-round-trip-{}", std::process::id());
+
+    // Serve a temporary named pipe, checking the lines it receives and acknowledging the install
+    let script = format!(
+        "$pipe = New-Object System.IO.Pipes.NamedPipeServerStream('{name}', 'InOut'); \
+         $pipe.WaitForConnection(); \
+         $reader = New-Object System.IO.StreamReader($pipe); \
+         $writer = New-Object System.IO.StreamWriter($pipe); \
+         $writer.AutoFlush = $true; \
+         if ($reader.ReadLine() -ne '%%>connect:global') {{ exit 1 }}; \
+         if ($reader.ReadLine() -ne '%%>install::call.route') {{ exit 2 }}; \
+         $writer.Write('%%<install:100:call.route:true' + [char]10); \
+         $pipe.WaitForPipeDrain(); \
+         $pipe.Dispose()"
+    );
+    let mut server = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .spawn()
+        .unwrap();
+
+    block_on(async {
+        // Wait for the server to create the pipe
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        let engine = loop {
+            match Engine::connect_named_pipe(&name, ConnectRole::Global, None).await {
+                Err(Error::Io(err))
+                    if err.kind() == io::ErrorKind::NotFound
+                        && std::time::Instant::now() < deadline =>
+                {
+                    futures_timer::Delay::new(Duration::from_millis(100)).await
+                }
+                engine => break engine.unwrap(),
+            }
+        };
+
+        assert!(engine.install(None, "call.route", None).await.unwrap());
+    });
+
+    assert!(server.wait().unwrap().success());
+}
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use futures::{FutureExt, future::BoxFuture};
+use futures_timer::Delay;
+
+/// The source of time of the engine, for the watchdog, the timeouts and the rate limiting.
+pub trait Timer: Send + Sync {
+    /// The current instant.
+    fn now(&self) -> Instant;
+
+    /// A future completing after `duration` has elapsed.
+    fn delay(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The [`Timer`] of the system, backed by [`Instant::now`] and [`Delay`].
+pub struct SystemTimer;
+
+impl Timer for SystemTimer {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn delay(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Delay::new(duration).boxed()
+    }
+}
+
+#[cfg(test)]
+pub use mock::MockTimer;
+
+#[cfg(test)]
+mod mock {
+    use std::{
+        sync::{Arc, Mutex},
+        task::{Poll, Waker},
+        time::{Duration, Instant},
+    };
+
+    use futures::{FutureExt, future::BoxFuture};
+
+    use super::Timer;
+
+    struct State {
+        now: Instant,
+        wakers: Vec<Waker>,
+    }
+
+    /// A [`Timer`] which only moves forward with [`MockTimer::advance`].
+    #[derive(Clone)]
+    pub struct MockTimer(Arc<Mutex<State>>);
+
+    impl MockTimer {
+        pub fn new() -> Self {
+            Self(Arc::new(Mutex::new(State {
+                now: Instant::now(),
+                wakers: Vec::new(),
+            })))
+        }
+
+        /// Move the time forward by `duration`, waking the pending delays.
+        pub fn advance(&self, duration: Duration) {
+            let wakers = {
+                let mut state = self.0.lock().unwrap();
+                state.now += duration;
+
+                std::mem::take(&mut state.wakers)
+            };
+
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+
+    impl Timer for MockTimer {
+        fn now(&self) -> Instant {
+            self.0.lock().unwrap().now
+        }
+
+        fn delay(&self, duration: Duration) -> BoxFuture<'static, ()> {
+            let state = self.0.clone();
+            let deadline = self.now() + duration;
+
+            futures::future::poll_fn(move |cx| {
+                let mut state = state.lock().unwrap();
+
+                if state.now >= deadline {
+                    Poll::Ready(())
+                } else {
+                    state.wakers.push(cx.waker().clone());
+
+                    Poll::Pending
+                }
+            })
+            .boxed()
+        }
+    }
+}
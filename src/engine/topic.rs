@@ -1,6 +1,4 @@
-use crate::wire::{
-    self, InstallAck, Message, MessageAck, QuitAck, SetLocalAck, UninstallAck, UnwatchAck, WatchAck,
-};
+use crate::wire::{self, ErrorIn};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Topic {
@@ -11,42 +9,151 @@ pub enum Topic {
     Watch,
     SetLocalAck(String),
     Message,
+    MessageNamed(String),
     MessageAck(String),
     QuitAck,
 
     Other,
 }
 
-impl subable::Topic for Topic {
+/// Split the leading keyword of the `line`, returning it along with
+/// an accessor to the decoded `index`th field following it.
+fn keyword(line: &str) -> (&str, impl FnMut(usize) -> Option<String>) {
+    let mut fields = line.split(':');
+    let keyword = fields.next().unwrap_or_default();
+
+    (keyword, move |index| {
+        fields
+            .nth(index)
+            .and_then(|field| wire::upcode::decode(field).ok())
+            .map(String::from)
+    })
+}
+
+impl Topic {
+    /// Identify the topic of the acknowledgement awaited for the `original` line
+    /// rejected by the engine, on a best-effort basis as the line may be malformed.
+    fn rejected(original: &str) -> Self {
+        let (keyword, mut field) = keyword(original);
+
+        match keyword {
+            "%%>install" => field(1).map(Topic::InstallAck),
+            "%%>uninstall" => field(0).map(Topic::UninstallAck),
+            "%%>watch" => field(0).map(Topic::WatchAck),
+            "%%>unwatch" => field(0).map(Topic::UnwatchAck),
+            "%%>setlocal" => field(0).map(Topic::SetLocalAck),
+            "%%>message" => field(0).map(Topic::MessageAck),
+            "%%>quit" => Some(Topic::QuitAck),
+            _ => None,
+        }
+        .unwrap_or(Topic::Other)
+    }
+}
+
+impl crate::subable::Topic for Topic {
     type Item = String;
 
+    /// Identify the topic from the keyword and correlation field of the item only,
+    /// it is then fully parsed by it's subscriber.
     fn topic(item: &Self::Item) -> Self {
-        if let Ok(msg) = wire::from_str::<InstallAck>(item) {
-            Topic::InstallAck(msg.name)
-        } else if let Ok(msg) = wire::from_str::<UninstallAck>(item) {
-            Topic::UninstallAck(msg.name)
-        } else if let Ok(msg) = wire::from_str::<WatchAck>(item) {
-            Topic::WatchAck(msg.name)
-        } else if let Ok(msg) = wire::from_str::<UnwatchAck>(item) {
-            Topic::UnwatchAck(msg.name)
-        } else if let Ok(msg) = wire::from_str::<SetLocalAck>(item) {
-            Topic::SetLocalAck(msg.name)
-        } else if wire::from_str::<Message>(item).is_ok() {
-            Topic::Message
-        } else if let Ok(msg) = wire::from_str::<MessageAck>(item) {
-            Topic::MessageAck(msg.id)
-        } else if wire::from_str::<QuitAck>(item).is_ok() {
-            Topic::QuitAck
-        } else {
-            Topic::Other
+        if let Some(ErrorIn { original }) = ErrorIn::parse(item) {
+            return Self::rejected(&original);
+        }
+
+        let (keyword, mut field) = keyword(item);
+
+        match keyword {
+            "%%<install" => field(1).map(Topic::InstallAck),
+            "%%<uninstall" => field(1).map(Topic::UninstallAck),
+            "%%<watch" => field(0).map(Topic::WatchAck),
+            "%%<unwatch" => field(0).map(Topic::UnwatchAck),
+            "%%<setlocal" => field(0).map(Topic::SetLocalAck),
+            "%%>message" => field(2).map(Topic::MessageNamed),
+            "%%<message" => field(0).map(Topic::MessageAck),
+            "%%<quit" => Some(Topic::QuitAck),
+            _ => None,
         }
+        .unwrap_or(Topic::Other)
     }
 
     fn fallback(self) -> Self {
         match self {
             // Fallback unhandled `MessageAck` as `Watch`
             Self::MessageAck(_) => Self::Watch,
+            // Fallback unhandled named `Message` as `Message`
+            Self::MessageNamed(_) => Self::Message,
             other => other,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subable::Topic as _;
+
+    fn topic(line: &str) -> Topic {
+        Topic::topic(&line.to_owned())
+    }
+
+    #[test]
+    fn it_classifies_lines() {
+        assert_eq!(
+            topic("%%<install:100:call.route:true"),
+            Topic::InstallAck("call.route".into())
+        );
+        assert_eq!(
+            topic("%%<uninstall:100:call.route:true"),
+            Topic::UninstallAck("call.route".into())
+        );
+        assert_eq!(
+            topic("%%<watch:call.answered:true"),
+            Topic::WatchAck("call.answered".into())
+        );
+        assert_eq!(
+            topic("%%<unwatch:call.answered:true"),
+            Topic::UnwatchAck("call.answered".into())
+        );
+        assert_eq!(
+            topic("%%<setlocal:engine.version:6.4.1:true"),
+            Topic::SetLocalAck("engine.version".into())
+        );
+        assert_eq!(
+            topic("%%>message:234479208:1095112795:call.route::caller=bob"),
+            Topic::MessageNamed("call.route".into())
+        );
+        assert_eq!(
+            topic("%%>message:234479208:1095112795:engine.timer::time=1095112795"),
+            Topic::MessageNamed("engine.timer".into())
+        );
+        assert_eq!(
+            topic("%%<message:yengine.1:true:call.route:sip/alice"),
+            Topic::MessageAck("yengine.1".into())
+        );
+        assert_eq!(topic("%%<quit"), Topic::QuitAck);
+        assert_eq!(
+            topic("Error in:%%>watch:call.answered"),
+            Topic::WatchAck("call.answered".into())
+        );
+    }
+
+    #[test]
+    fn it_classifies_escaped_fields() {
+        assert_eq!(
+            topic("%%<setlocal:config.general%zmodules:false:true"),
+            Topic::SetLocalAck("config.general:modules".into())
+        );
+    }
+
+    #[test]
+    fn it_classifies_malformed_lines() {
+        assert_eq!(topic(""), Topic::Other);
+        assert_eq!(topic("   "), Topic::Other);
+        assert_eq!(topic("%%<install:100"), Topic::Other);
+        assert_eq!(topic("%%>message:234479208:1095112795"), Topic::Other);
+        assert_eq!(topic("%%<setlocal:bad%"), Topic::Other);
+        assert_eq!(topic("%%>install:100:call.route"), Topic::Other);
+        assert_eq!(topic("Error in:garbage"), Topic::Other);
+        assert_eq!(topic("hello"), Topic::Other);
+    }
+}
@@ -5,9 +5,20 @@
 #![warn(missing_docs, clippy::unimplemented)]
 
 pub mod engine;
+pub mod messages;
 pub mod wire;
 
 mod module;
-pub use module::Module;
+pub use module::{Module, Router};
+
+mod subable;
+
+#[cfg(feature = "blocking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
+pub mod blocking;
+
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod mock;
 
 pub use engine::Engine;
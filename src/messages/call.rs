@@ -0,0 +1,114 @@
+use crate::wire::{Message, Params};
+
+view! {
+    /// A view over a `call.route` [`Message`], requesting the routing of a call.
+    ///
+    /// The call is routed by acknowledging the message as _processed_,
+    /// with the target of the call as it's return value.
+    CallRoute => "call.route"
+}
+
+impl<'m> CallRoute<'m> {
+    /// The caller of the call, `caller`.
+    pub fn caller(&self) -> Option<&'m str> {
+        self.param("caller")
+    }
+
+    /// The called party of the call, `called`.
+    pub fn called(&self) -> Option<&'m str> {
+        self.param("called")
+    }
+
+    /// The billing identifier of the call, `billid`.
+    pub fn billid(&self) -> Option<&'m str> {
+        self.param("billid")
+    }
+
+    /// All the parameters of the message.
+    pub fn kv(&self) -> &'m Params {
+        &self.message.kv
+    }
+}
+
+view! {
+    /// A view over a `call.execute` [`Message`], requesting the execution of a call
+    /// by the channel designated in `callto`.
+    CallExecute => "call.execute"
+}
+
+impl<'m> CallExecute<'m> {
+    /// The caller of the call, `caller`.
+    pub fn caller(&self) -> Option<&'m str> {
+        self.param("caller")
+    }
+
+    /// The called party of the call, `called`.
+    pub fn called(&self) -> Option<&'m str> {
+        self.param("called")
+    }
+
+    /// The target the call is executed to, `callto`.
+    pub fn callto(&self) -> Option<&'m str> {
+        self.param("callto")
+    }
+
+    /// The billing identifier of the call, `billid`.
+    pub fn billid(&self) -> Option<&'m str> {
+        self.param("billid")
+    }
+
+    /// All the parameters of the message.
+    pub fn kv(&self) -> &'m Params {
+        &self.message.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire;
+
+    #[test]
+    fn it_parses_route() {
+        let message = wire::from_str::<Message>(
+            "%%>message:yengine.1.1:1095112795:call.route::billid=1095112794-1:called=99991001:caller=123:context=default",
+        )
+        .unwrap();
+        let route = CallRoute::new(&message).unwrap();
+
+        assert_eq!(route.caller(), Some("123"));
+        assert_eq!(route.called(), Some("99991001"));
+        assert_eq!(route.billid(), Some("1095112794-1"));
+        assert_eq!(
+            route.kv().get("context").map(String::as_str),
+            Some("default")
+        );
+        assert!(CallExecute::new(&message).is_none());
+    }
+
+    #[test]
+    fn it_parses_execute() {
+        let message = wire::from_str::<Message>(
+            "%%>message:yengine.1.1:1095112795:call.execute::billid=1095112794-1:called=99991001:caller=123:callto=sip/sip%z99991001@example.org",
+        )
+        .unwrap();
+        let execute = CallExecute::new(&message).unwrap();
+
+        assert_eq!(execute.caller(), Some("123"));
+        assert_eq!(execute.called(), Some("99991001"));
+        assert_eq!(execute.callto(), Some("sip/sip:99991001@example.org"));
+        assert_eq!(execute.billid(), Some("1095112794-1"));
+        assert!(CallRoute::new(&message).is_none());
+    }
+
+    #[test]
+    fn it_handles_absent_params() {
+        let message =
+            wire::from_str::<Message>("%%>message:yengine.1.1:1095112795:call.execute:").unwrap();
+        let execute = CallExecute::new(&message).unwrap();
+
+        assert_eq!(execute.caller(), None);
+        assert_eq!(execute.callto(), None);
+        assert!(execute.kv().is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use crate::wire::Message;
+
+view! {
+    /// A view over a `call.cdr` [`Message`], emitted for _call detail records_.
+    CallCdr => "call.cdr"
+}
+
+impl<'m> CallCdr<'m> {
+    /// The billing identifier of the call, `billid`.
+    pub fn billid(&self) -> Option<&'m str> {
+        self.param("billid")
+    }
+
+    /// Whether the call detail record is tracked, `cdrtrack`.
+    pub fn cdrtrack(&self) -> Option<bool> {
+        self.message.get_bool("cdrtrack")
+    }
+
+    /// The caller of the call, `caller`.
+    pub fn caller(&self) -> Option<&'m str> {
+        self.param("caller")
+    }
+
+    /// The called party of the call, `called`.
+    pub fn called(&self) -> Option<&'m str> {
+        self.param("called")
+    }
+
+    /// The total duration of the call, `duration`, in (decimal) seconds on the wire.
+    pub fn duration(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(self.param("duration")?.parse().ok()?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire;
+
+    #[test]
+    fn it_parses_cdr() {
+        let message = wire::from_str::<Message>(
+            "%%>message:yengine.1.1:1095112795:call.cdr::billid=1095112794-1:called=99991001:caller=123:cdrtrack=false:duration=12.345",
+        )
+        .unwrap();
+        let cdr = CallCdr::new(&message).unwrap();
+
+        assert_eq!(cdr.billid(), Some("1095112794-1"));
+        assert_eq!(cdr.cdrtrack(), Some(false));
+        assert_eq!(cdr.caller(), Some("123"));
+        assert_eq!(cdr.called(), Some("99991001"));
+        assert_eq!(cdr.duration(), Some(Duration::from_millis(12345)));
+    }
+
+    #[test]
+    fn it_handles_absent_params() {
+        let message =
+            wire::from_str::<Message>("%%>message:yengine.1.1:1095112795:call.cdr::billid=1")
+                .unwrap();
+        let cdr = CallCdr::new(&message).unwrap();
+
+        assert_eq!(cdr.billid(), Some("1"));
+        assert_eq!(cdr.cdrtrack(), None);
+        assert_eq!(cdr.caller(), None);
+        assert_eq!(cdr.duration(), None);
+    }
+
+    #[test]
+    fn it_rejects_other_messages() {
+        let message =
+            wire::from_str::<Message>("%%>message:yengine.1.1:1095112795:engine.timer:").unwrap();
+
+        assert!(CallCdr::new(&message).is_none());
+    }
+}
@@ -0,0 +1,67 @@
+//! Typed views over well-known engine [`Message`]s and parameters.
+//!
+//! The views are zero-cost wrappers over a borrowed [`Message`],
+//! providing typed accessors to the conventional parameters of the message.
+
+use crate::wire::Message;
+
+/// Define a view named `$view` over the messages named `$name`,
+/// with it's `NAME`, it's `new` constructor and a `param` accessor,
+/// `Message` being in scope where it's invoked.
+macro_rules! view {
+    ($(#[$meta:meta])* $view:ident => $name:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy)]
+        pub struct $view<'m> {
+            message: &'m Message,
+        }
+
+        impl<'m> $view<'m> {
+            /// The name of the message this view applies to.
+            pub const NAME: &'static str = $name;
+
+            #[doc = concat!("Create the view over the `message`, if it's named `", $name, "`.")]
+            pub fn new(message: &'m Message) -> Option<Self> {
+                (message.name == Self::NAME).then_some(Self { message })
+            }
+
+            fn param(&self, key: &str) -> Option<&'m str> {
+                self.message.get(key)
+            }
+        }
+    };
+}
+
+mod call;
+pub use call::{CallExecute, CallRoute};
+
+mod cdr;
+pub use cdr::CallCdr;
+
+mod timer;
+pub use timer::EngineTimer;
+
+mod resource;
+pub use resource::{ResourceNotify, ResourceSubscribe};
+
+mod trace;
+pub use trace::TraceContext;
+
+/// An extension of [`Message`] with accessors to it's typed views and conventional parameters.
+pub trait MessageExt {
+    /// View the message as an `engine.timer` tick, if it is one.
+    fn as_timer(&self) -> Option<EngineTimer<'_>>;
+
+    /// Extract the tracing context of the message, if any.
+    fn trace_context(&self) -> Option<TraceContext>;
+}
+
+impl MessageExt for Message {
+    fn as_timer(&self) -> Option<EngineTimer<'_>> {
+        EngineTimer::new(self)
+    }
+
+    fn trace_context(&self) -> Option<TraceContext> {
+        TraceContext::parse(self.get(TraceContext::PARAM)?)
+    }
+}
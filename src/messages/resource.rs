@@ -0,0 +1,122 @@
+use crate::wire::{Message, Params};
+
+#[cfg(doc)]
+use crate::engine::Engine;
+
+view! {
+    /// A view over a `resource.notify` [`Message`], notifying of a _presence_ change.
+    ///
+    /// Parameters conventions:
+    /// - `contact` - the contact (URI or JID) the notification is about,
+    /// - `status` - the presence status, like `online` or `offline`,
+    /// - `subscription` - the subscription state between the parties, if known.
+    ResourceNotify => "resource.notify"
+}
+
+impl<'m> ResourceNotify<'m> {
+    /// Build the parameters of a `resource.notify` for the `contact` with the provided `status`,
+    /// to be sent with [`Engine::message`].
+    pub fn params(contact: impl Into<String>, status: impl Into<String>) -> Params {
+        Params::from([
+            ("contact".into(), contact.into()),
+            ("status".into(), status.into()),
+        ])
+    }
+
+    /// The contact the notification is about, `contact`.
+    pub fn contact(&self) -> Option<&'m str> {
+        self.param("contact")
+    }
+
+    /// The presence status of the contact, `status`.
+    pub fn status(&self) -> Option<&'m str> {
+        self.param("status")
+    }
+
+    /// The subscription state of the contact, `subscription`.
+    pub fn subscription(&self) -> Option<&'m str> {
+        self.param("subscription")
+    }
+}
+
+view! {
+    /// A view over a `resource.subscribe` [`Message`], requesting a _presence_ subscription change.
+    ///
+    /// Parameters conventions:
+    /// - `contact` - the contact (URI or JID) to (un)subscribe to,
+    /// - `subscription` - the requested subscription operation, like `subscribe` or `unsubscribe`.
+    ///
+    /// The subscription is accepted by acknowledging the message as _processed_.
+    ResourceSubscribe => "resource.subscribe"
+}
+
+impl<'m> ResourceSubscribe<'m> {
+    /// Build the parameters of a `resource.subscribe` for the `contact` with the provided `subscription`,
+    /// to be sent with [`Engine::message`].
+    pub fn params(contact: impl Into<String>, subscription: impl Into<String>) -> Params {
+        Params::from([
+            ("contact".into(), contact.into()),
+            ("subscription".into(), subscription.into()),
+        ])
+    }
+
+    /// The contact to (un)subscribe to, `contact`.
+    pub fn contact(&self) -> Option<&'m str> {
+        self.param("contact")
+    }
+
+    /// The requested subscription operation, `subscription`.
+    pub fn subscription(&self) -> Option<&'m str> {
+        self.param("subscription")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::wire;
+
+    #[test]
+    fn it_parses_notify() {
+        let message = wire::from_str::<Message>(
+            "%%>message:yengine.1.1:1095112795:resource.notify::contact=alice@example.org:status=online:subscription=both",
+        )
+        .unwrap();
+        let notify = ResourceNotify::new(&message).unwrap();
+
+        assert_eq!(notify.contact(), Some("alice@example.org"));
+        assert_eq!(notify.status(), Some("online"));
+        assert_eq!(notify.subscription(), Some("both"));
+        assert!(ResourceSubscribe::new(&message).is_none());
+    }
+
+    #[test]
+    fn it_parses_subscribe() {
+        let message = wire::from_str::<Message>(
+            "%%>message:yengine.1.1:1095112795:resource.subscribe::contact=bob@example.org:subscription=subscribe",
+        )
+        .unwrap();
+        let subscribe = ResourceSubscribe::new(&message).unwrap();
+
+        assert_eq!(subscribe.contact(), Some("bob@example.org"));
+        assert_eq!(subscribe.subscription(), Some("subscribe"));
+    }
+
+    #[test]
+    fn it_builds_params() {
+        let message = Message {
+            id: "yengine.1.1".into(),
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795),
+            name: ResourceNotify::NAME.into(),
+            retvalue: Default::default(),
+            kv: ResourceNotify::params("alice@example.org", "offline"),
+        };
+
+        assert_eq!(
+            wire::to_string(&message),
+            "%%>message:yengine.1.1:1095112795:resource.notify::contact=alice@example.org:status=offline"
+        );
+    }
+}
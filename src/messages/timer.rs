@@ -0,0 +1,70 @@
+use std::time::{Duration, SystemTime};
+
+use crate::wire::Message;
+
+view! {
+    /// A view over an `engine.timer` [`Message`], emitted by the engine every second.
+    EngineTimer => "engine.timer"
+}
+
+impl<'m> EngineTimer<'m> {
+    /// The time of the tick, `time`, in seconds since the epoch on the wire,
+    /// or the time the message was created if absent.
+    pub fn time(&self) -> SystemTime {
+        self.param("time")
+            .and_then(|time| time.parse().ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap_or(self.message.time)
+    }
+
+    /// The name of the node running the engine, `nodename`.
+    pub fn nodename(&self) -> Option<&'m str> {
+        self.param("nodename")
+    }
+
+    /// The identifier of the engine's current run, `runid`.
+    pub fn runid(&self) -> Option<&'m str> {
+        self.param("runid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{messages::MessageExt, wire};
+
+    #[test]
+    fn it_parses_timer() {
+        let message = wire::from_str::<Message>(
+            "%%>message:234479208:1095112795:engine.timer::time=1095112796:nodename=pbx1:runid=1095112700",
+        )
+        .unwrap();
+        let timer = message.as_timer().unwrap();
+
+        assert_eq!(
+            timer.time(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1095112796)
+        );
+        assert_eq!(timer.nodename(), Some("pbx1"));
+        assert_eq!(timer.runid(), Some("1095112700"));
+    }
+
+    #[test]
+    fn it_handles_absent_params() {
+        let message =
+            wire::from_str::<Message>("%%>message:234479208:1095112795:engine.timer:").unwrap();
+        let timer = message.as_timer().unwrap();
+
+        assert_eq!(timer.time(), message.time);
+        assert_eq!(timer.nodename(), None);
+        assert_eq!(timer.runid(), None);
+    }
+
+    #[test]
+    fn it_rejects_other_messages() {
+        let message =
+            wire::from_str::<Message>("%%>message:234479208:1095112795:call.cdr:").unwrap();
+
+        assert!(message.as_timer().is_none());
+    }
+}
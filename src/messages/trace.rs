@@ -0,0 +1,115 @@
+use std::fmt;
+
+use crate::wire::Params;
+
+#[cfg(doc)]
+use crate::wire::Message;
+
+/// A distributed tracing context, carried in the `traceparent` parameter of a [`Message`]
+/// using the _W3C Trace Context_ format: `<version>-<trace-id>-<parent-id>-<trace-flags>`.
+///
+/// _see <https://www.w3.org/TR/trace-context/#traceparent-header>_.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The 16-byte identifier of the whole trace.
+    pub trace_id: u128,
+
+    /// The 8-byte identifier of the parent span.
+    pub span_id: u64,
+
+    /// The trace flags, `0x01` being _sampled_.
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// The name of the parameter carrying the tracing context.
+    pub const PARAM: &'static str = "traceparent";
+
+    /// Insert the tracing context in the parameters of an outgoing message.
+    pub fn with_trace_context(&self, mut kv: Params) -> Params {
+        kv.insert(Self::PARAM.into(), self.to_string());
+        kv
+    }
+
+    /// Parse the tracing context from it's `traceparent` representation.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split('-');
+
+        let (Some("00"), Some(trace_id), Some(span_id), Some(flags), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return None;
+        };
+
+        if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::{
+        messages::MessageExt,
+        wire::{self, Message},
+    };
+
+    #[test]
+    fn it_round_trips() {
+        let ctx = TraceContext {
+            trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+            span_id: 0x00f067aa0ba902b7,
+            flags: 0x01,
+        };
+        let message = Message {
+            id: "yengine.1.1".into(),
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795),
+            name: "call.route".into(),
+            retvalue: Default::default(),
+            kv: ctx.with_trace_context(Default::default()),
+        };
+
+        let line = wire::to_string(&message);
+        assert_eq!(
+            line,
+            "%%>message:yengine.1.1:1095112795:call.route::traceparent=00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(
+            wire::from_str::<Message>(&line).unwrap().trace_context(),
+            Some(ctx)
+        );
+    }
+
+    #[test]
+    fn it_rejects_malformed() {
+        assert_eq!(TraceContext::parse(""), None);
+        assert_eq!(TraceContext::parse("00-abc-def-01"), None);
+        assert_eq!(
+            TraceContext::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            None
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! In-memory transport to test [`Module`]s and [`Engine`] consumers
+//! deterministically, without any runtime, stdio or socket.
+//!
+//! Sessions with a real telephony engine may also be captured with a [`SessionRecorder`]
+//! to be replayed later against a [`MockEngine`], see [`MockEngine::replay`].
+//!
+//! ```
+//! use futures::executor::block_on;
+//! use yengine::mock::MockEngine;
+//!
+//! let (engine, mut mock) = MockEngine::new();
+//!
+//! block_on(async {
+//!     mock.inject("%%<install:100:engine.timer:true").await;
+//!     assert!(engine.install(100, "engine.timer", None).await.unwrap());
+//!
+//!     mock.expect_sent("%%>install:100:engine.timer").await;
+//! });
+//! ```
+
+use std::{
+    collections::VecDeque,
+    io::{self, IoSlice},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, TryStreamExt,
+    io::{BufReader, Lines},
+};
+
+use crate::Engine;
+
+mod session;
+pub use session::{Recorded, SessionRecorder};
+
+#[cfg(doc)]
+use crate::Module;
+
+#[derive(Default)]
+pub(crate) struct State {
+    buf: VecDeque<u8>,
+    closed: bool,
+    pub(crate) flushes: usize,
+    pub(crate) writes: usize,
+    waker: Option<Waker>,
+}
+
+/// The reading end of an in-memory pipe, created with [`pipe`].
+pub struct PipeReader(pub(crate) Arc<Mutex<State>>);
+
+impl AsyncRead for PipeReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.0.lock().unwrap();
+
+        if state.buf.is_empty() {
+            if state.closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let len = buf.len().min(state.buf.len());
+        for (dst, src) in buf.iter_mut().zip(state.buf.drain(..len)) {
+            *dst = src;
+        }
+
+        Poll::Ready(Ok(len))
+    }
+}
+
+/// The writing end of an in-memory pipe, created with [`pipe`].
+///
+/// Dropping it closes the pipe, and the reading end then reaches it's end.
+pub struct PipeWriter(Arc<Mutex<State>>);
+
+impl PipeWriter {
+    /// Close the pipe, letting the reading end reach it's end once drained.
+    pub fn shut(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl AsyncWrite for PipeWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.0.lock().unwrap();
+
+        state.writes += 1;
+        for buf in bufs {
+            state.buf.extend(buf.iter());
+        }
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+
+        Poll::Ready(Ok(bufs.iter().map(|buf| buf.len()).sum()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.0.lock().unwrap().flushes += 1;
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.shut();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.shut();
+    }
+}
+
+/// Create an unbounded in-memory pipe, the bytes written to the [`PipeWriter`]
+/// are read from the [`PipeReader`] in order.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let state = Arc::new(Mutex::new(State::default()));
+
+    (PipeReader(state.clone()), PipeWriter(state))
+}
+
+/// The telephony engine's side of an [`Engine`] connected with in-memory pipes,
+/// to push canned lines to the [`Engine`] and assert on the lines it sent.
+pub struct MockEngine {
+    inject: PipeWriter,
+    sent: Lines<BufReader<PipeReader>>,
+}
+
+impl MockEngine {
+    /// Create an [`Engine`] connected to a new [`MockEngine`].
+    pub fn new() -> (Engine<PipeReader, PipeWriter>, Self) {
+        let (rx, inject) = pipe();
+        let (sent, tx) = pipe();
+
+        (
+            Engine::from_io(rx, tx),
+            Self {
+                inject,
+                sent: BufReader::new(sent).lines(),
+            },
+        )
+    }
+
+    /// Push the `line` to the [`Engine`], as if sent by the telephony engine.
+    pub async fn inject(&mut self, line: impl AsRef<str>) {
+        self.inject
+            .write_all(format!("{}\n", line.as_ref()).as_bytes())
+            .await
+            .expect("writing to an in-memory pipe never fails");
+    }
+
+    /// Close the connection to the [`Engine`], which then reaches the end of it's input.
+    pub fn shut(&self) {
+        self.inject.shut();
+    }
+
+    /// Wait for the next line sent by the [`Engine`],
+    /// or `None` if the [`Engine`] has been dropped.
+    pub async fn next_sent(&mut self) -> Option<String> {
+        self.sent
+            .try_next()
+            .await
+            .expect("reading from an in-memory pipe never fails")
+    }
+
+    /// Wait for the next line sent by the [`Engine`] and assert it is the `line`.
+    ///
+    /// ## Panics
+    ///
+    /// If the sent line differs from the `line`, or if the [`Engine`] has been dropped.
+    pub async fn expect_sent(&mut self, line: impl AsRef<str>) {
+        let sent = self.next_sent().await;
+
+        assert_eq!(sent.as_deref(), Some(line.as_ref()), "unexpected sent line");
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    io::{self, BufRead, Write},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::SystemTime,
+};
+
+use futures::{AsyncRead, AsyncWrite};
+
+use super::MockEngine;
+
+/// The direction of a recorded line, as the prefix of it's record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// The line was received from the telephony engine, recorded as `< line`.
+    Inbound,
+
+    /// The line was sent to the telephony engine, recorded as `> line`.
+    Outbound,
+}
+
+impl Direction {
+    fn prefix(self) -> &'static str {
+        match self {
+            Self::Inbound => "<",
+            Self::Outbound => ">",
+        }
+    }
+}
+
+struct Log<W> {
+    writer: W,
+    clock: fn() -> SystemTime,
+    inbound: Vec<u8>,
+    outbound: Vec<u8>,
+}
+
+impl<W: Write> Log<W> {
+    /// Append the `bytes` flowing in the `direction`, recording each completed line.
+    fn feed(&mut self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        let pending = match direction {
+            Direction::Inbound => &mut self.inbound,
+            Direction::Outbound => &mut self.outbound,
+        };
+        pending.extend_from_slice(bytes);
+
+        while let Some(pos) = memchr::memchr(b'\n', pending) {
+            let mut line = pending.drain(..=pos).collect::<Vec<_>>();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+
+            let timestamp = (self.clock)()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            writeln!(
+                self.writer,
+                "{}.{:06} {} {}",
+                timestamp.as_secs(),
+                timestamp.subsec_micros(),
+                direction.prefix(),
+                String::from_utf8_lossy(&line)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A recorder of every line exchanged on an [`Engine`](crate::Engine)'s I/O,
+/// logged to a writer to be replayed later with [`MockEngine::replay`].
+///
+/// Each line is recorded in order with it's UNIX timestamp and direction,
+/// as `1095112795.000000 < line` when received from the telephony engine
+/// and `1095112795.000000 > line` when sent to it.
+pub struct SessionRecorder<W> {
+    log: Arc<Mutex<Log<W>>>,
+}
+
+impl<W: Write> SessionRecorder<W> {
+    /// Create a recorder logging the lines to the `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            log: Arc::new(Mutex::new(Log {
+                writer,
+                clock: SystemTime::now,
+                inbound: Default::default(),
+                outbound: Default::default(),
+            })),
+        }
+    }
+
+    /// Wrap the `rx` and `tx` halves of the I/O, to be passed to [`Engine::from_io`](crate::Engine::from_io).
+    pub fn record<I, O>(&self, rx: I, tx: O) -> (Recorded<I, W>, Recorded<O, W>) {
+        (
+            Recorded {
+                io: rx,
+                log: self.log.clone(),
+            },
+            Recorded {
+                io: tx,
+                log: self.log.clone(),
+            },
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_clock(&self, clock: fn() -> SystemTime) {
+        self.log.lock().unwrap().clock = clock;
+    }
+
+    /// Take the writer back from the recorder, or `None` if the recorded I/O is still alive.
+    pub fn into_writer(self) -> Option<W> {
+        Arc::into_inner(self.log).map(|log| log.into_inner().unwrap().writer)
+    }
+}
+
+/// A half of an I/O recorded by a [`SessionRecorder`].
+pub struct Recorded<T, W> {
+    io: T,
+    log: Arc<Mutex<Log<W>>>,
+}
+
+impl<I: AsyncRead + Unpin, W: Write> AsyncRead for Recorded<I, W> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = futures::ready!(Pin::new(&mut self.io).poll_read(cx, buf))?;
+        self.log
+            .lock()
+            .unwrap()
+            .feed(Direction::Inbound, &buf[..len])?;
+
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<O: AsyncWrite + Unpin, W: Write> AsyncWrite for Recorded<O, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = futures::ready!(Pin::new(&mut self.io).poll_write(cx, buf))?;
+        self.log
+            .lock()
+            .unwrap()
+            .feed(Direction::Outbound, &buf[..len])?;
+
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        futures::ready!(Pin::new(&mut self.io).poll_flush(cx))?;
+
+        Poll::Ready(self.log.lock().unwrap().writer.flush())
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
+
+impl MockEngine {
+    /// Replay a session recorded with a [`SessionRecorder`] from the `reader`,
+    /// injecting the inbound lines and checking the outbound lines in order,
+    /// then closing the connection once the session ends or diverges.
+    ///
+    /// The timestamps of the records are ignored, the lines being replayed as fast as possible,
+    /// and may be omitted, as in `< line` and `> line`.
+    ///
+    /// This errors with [`io::ErrorKind::InvalidData`] if a record is malformed or
+    /// if the [`Engine`](crate::Engine) sent a line other than the recorded one.
+    pub async fn replay(&mut self, reader: impl BufRead) -> io::Result<()> {
+        let result = self.replay_records(reader).await;
+        self.shut();
+
+        result
+    }
+
+    async fn replay_records(&mut self, reader: impl BufRead) -> io::Result<()> {
+        for record in reader.lines() {
+            let record = record?;
+
+            // Skip the timestamp of the record, if any
+            let entry = match record.split_once(' ') {
+                Some((timestamp, entry)) if timestamp.parse::<f64>().is_ok() => entry,
+                _ => &record,
+            };
+
+            match entry.split_once(' ') {
+                Some(("<", line)) => self.inject(line).await,
+                Some((">", line)) => {
+                    let sent = self.next_sent().await;
+
+                    if sent.as_deref() != Some(line) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("expected `{line}` to be sent, got {sent:?}"),
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("malformed session record `{record}`"),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
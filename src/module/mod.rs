@@ -0,0 +1,215 @@
+use std::{any::Any, panic::AssertUnwindSafe, pin::pin};
+
+use futures::{
+    AsyncRead, AsyncWrite, FutureExt, StreamExt, TryStreamExt,
+    future::{self, Either},
+    stream::FuturesUnordered,
+};
+
+use crate::{
+    engine::{Engine, Error, Request},
+    wire::MessageAck,
+};
+
+mod router;
+pub use router::Router;
+
+/// Abstraction of an external [`Module`].
+pub trait Module {
+    /// Errors which may occur while processing messages.
+    type Error: From<Error>;
+
+    /// Whether [`Module::run`] catches the panics of [`Module::on_message`],
+    /// acknowledging the message as not processed and carrying on with the next ones,
+    /// rather than letting the panic unwind.
+    const CATCH_PANICS: bool = true;
+
+    /// The count of messages [`Module::run`] processes concurrently, `1` by default.
+    ///
+    /// As the engine dispatches a message to the next handler only once acknowledged,
+    /// ordering between messages processed concurrently is lost: the messages for which
+    /// ordering matters must be excluded with [`Module::is_concurrent`].
+    const CONCURRENCY: usize = 1;
+
+    /// Whether the `request` may be processed concurrently with other messages by [`Module::run`],
+    /// otherwise it is processed alone, once the previous messages are acknowledged.
+    fn is_concurrent(&self, _request: &Request) -> bool {
+        true
+    }
+
+    /// Install handlers and watches for processing.
+    ///
+    /// After installing your handlers and watches,
+    /// you might want to **.await** on some signal
+    /// (`SIGINT` for example) and then call [`Engine::quit`].
+    /// This will make the engine stop sending us messages
+    /// and exit upon processing the last one.
+    fn install<I, O>(&self, engine: &Engine<I, O>) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin;
+
+    /// Process an incoming `watch` from the engine.
+    fn on_watch<I, O>(
+        &self,
+        _engine: &Engine<I, O>,
+        _watch: MessageAck,
+    ) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+    {
+        futures::future::ok(())
+    }
+
+    /// Process an incoming `message` from the engine.
+    fn on_message<I, O>(
+        &self,
+        _engine: &Engine<I, O>,
+        _request: &mut Request,
+    ) -> impl Future<Output = Result<bool, Self::Error>>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+    {
+        futures::future::ok(false)
+    }
+
+    /// Install the handlers and process the messages and watches from the engine until it stops sending them,
+    /// acknowledging each message with the result of [`Module::on_message`].
+    ///
+    /// Watches are processed one at a time, and messages up to [`Module::CONCURRENCY`]
+    /// at a time, see [`Module::is_concurrent`].
+    /// A panicking [`Module::on_message`] is logged as [`Error::HandlerPanicked`]
+    /// and it's message acknowledged as not processed, see [`Module::CATCH_PANICS`].
+    ///
+    /// When [`Module::on_message`] errors, it's message is acknowledged as not processed,
+    /// and the error is returned once the other running messages are processed and acknowledged.
+    fn run<I, O>(&self, engine: &Engine<I, O>) -> impl Future<Output = Result<(), Self::Error>>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+    {
+        async move {
+            let process = async {
+                let mut messages = pin!(engine.messages());
+                let mut running = FuturesUnordered::new();
+
+                let result: Result<(), Self::Error> = async {
+                    loop {
+                        // Keep processing the running messages while waiting for the next one
+                        let next = if running.is_empty() {
+                            messages.try_next().await
+                        } else {
+                            match future::select(messages.try_next(), running.next()).await {
+                                Either::Left((next, _)) => next,
+                                Either::Right((done, _)) => {
+                                    done.expect("running messages are not empty")?;
+                                    continue;
+                                }
+                            }
+                        };
+
+                        let req = match next {
+                            Ok(Some(req)) => req,
+                            Ok(None) | Err(Error::UnexpectedEof) => break,
+                            Err(err) => return Err(err.into()),
+                        };
+
+                        if self.is_concurrent(&req) {
+                            running.push(process(self, engine, req));
+
+                            while running.len() >= Self::CONCURRENCY.max(1) {
+                                running
+                                    .next()
+                                    .await
+                                    .expect("running messages are not empty")?;
+                            }
+                        } else {
+                            // Process the message alone, once the running ones are done
+                            while let Some(done) = running.next().await {
+                                done?;
+                            }
+
+                            process(self, engine, req).await?;
+                        }
+                    }
+
+                    Ok(())
+                }
+                .await;
+
+                // Let the messages still running be acknowledged, even when bailing out on an error
+                let mut drained = Ok(());
+                while let Some(done) = running.next().await {
+                    drained = drained.and(done);
+                }
+
+                result.and(drained)
+            };
+
+            let watch = engine
+                .watches()
+                .err_into()
+                .try_for_each(|watch| self.on_watch(engine, watch));
+
+            futures::try_join!(self.install(engine), process, watch)?;
+
+            tracing::debug!("processed all messages, exiting");
+
+            Ok(())
+        }
+    }
+}
+
+/// Process the `req` with [`Module::on_message`] and acknowledge it with the result,
+/// or as not processed if it errored.
+async fn process<M, I, O>(
+    module: &M,
+    engine: &Engine<I, O>,
+    mut req: Request,
+) -> Result<(), M::Error>
+where
+    M: Module + ?Sized,
+    I: AsyncRead + Send + Unpin,
+    O: AsyncWrite + Send + Unpin,
+{
+    let processed = if M::CATCH_PANICS {
+        match AssertUnwindSafe(module.on_message(engine, &mut req))
+            .catch_unwind()
+            .await
+        {
+            Ok(processed) => processed,
+            Err(panic) => {
+                let err = Error::HandlerPanicked {
+                    id: req.id.clone(),
+                    panic: panic_message(&*panic),
+                };
+                tracing::error!("{err}");
+
+                Ok(false)
+            }
+        }
+    } else {
+        module.on_message(engine, &mut req).await
+    };
+
+    match processed {
+        Ok(processed) => Ok(engine.ack(req, processed).await?),
+        Err(err) => {
+            // Pass the message to the next handler before bailing out
+            engine.ack(req, false).await?;
+
+            Err(err)
+        }
+    }
+}
+
+/// Extract the message of a panic from it's `payload`, if any.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "<non-string panic payload>".into())
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeSet;
+
+use futures::{AsyncRead, AsyncWrite, future::BoxFuture};
+
+use crate::{
+    engine::{Engine, Error, Request, Result},
+    wire::MessageName,
+};
+
+#[cfg(doc)]
+use super::Module;
+
+type Route<E> =
+    Box<dyn for<'r> Fn(&'r mut Request) -> BoxFuture<'r, Result<bool, E>> + Send + Sync>;
+
+/// A dispatch table of message handlers, routing a [`Request`] by it's message name,
+/// to be used from [`Module::on_message`].
+///
+/// Multiple handlers may be registered for the same name,
+/// they are then tried in registration order until one processes the message.
+pub struct Router<E = Error> {
+    routes: Vec<(String, Route<E>)>,
+}
+
+impl<E> Default for Router<E> {
+    fn default() -> Self {
+        Self {
+            routes: Default::default(),
+        }
+    }
+}
+
+impl<E> Router<E> {
+    /// Register the `handler` for the messages named `name`.
+    ///
+    /// The `handler` returns whether the message has been processed,
+    /// as with [`Module::on_message`].
+    pub fn on<F>(mut self, name: impl Into<MessageName>, handler: F) -> Self
+    where
+        F: for<'r> Fn(&'r mut Request) -> BoxFuture<'r, Result<bool, E>> + Send + Sync + 'static,
+    {
+        self.routes.push((name.into().into(), Box::new(handler)));
+        self
+    }
+
+    /// The names of the messages with a registered handler.
+    pub fn names(&self) -> BTreeSet<&str> {
+        self.routes.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Dispatch the `req` to the handlers registered for it's name,
+    /// returning `false` if none of them processed it.
+    pub async fn route(&self, req: &mut Request) -> Result<bool, E> {
+        let name = req.name.clone();
+
+        for (_, handler) in self.routes.iter().filter(|(route, _)| *route == name) {
+            if handler(req).await? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Request the engine to install a message handler with the provided `priority`
+    /// for each of the names with a registered handler, returning whether they were all installed.
+    pub async fn install<I, O>(
+        &self,
+        engine: &Engine<I, O>,
+        priority: impl Into<Option<u64>>,
+    ) -> Result<bool>
+    where
+        I: AsyncRead + Send + Unpin,
+        O: AsyncWrite + Send + Unpin,
+    {
+        let priority = priority.into();
+        let mut success = true;
+
+        for name in self.names() {
+            success &= engine.install(priority, name, None).await?;
+        }
+
+        Ok(success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, executor::block_on};
+
+    use super::*;
+    use crate::wire::{self, Message};
+
+    fn request(line: &str) -> Request {
+        Request::new(wire::from_str::<Message>(line).unwrap())
+    }
+
+    fn router() -> Router {
+        Router::default()
+            .on("call.route", |req| {
+                async move {
+                    if req.get("called") != Some("alice") {
+                        return Ok(false);
+                    }
+
+                    req.set_retvalue("sip/alice");
+                    Ok(true)
+                }
+                .boxed()
+            })
+            .on("call.route", |req| {
+                async move {
+                    req.set_retvalue("sip/voicemail");
+                    Ok(true)
+                }
+                .boxed()
+            })
+            .on("engine.timer", |_| async { Ok(false) }.boxed())
+    }
+
+    #[test]
+    fn it_routes_matched_names() {
+        let mut req = request("%%>message:1:1095112795:call.route::called=alice");
+
+        assert!(block_on(router().route(&mut req)).unwrap());
+        assert_eq!(req.retvalue, "sip/alice");
+    }
+
+    #[test]
+    fn it_ignores_unmatched_names() {
+        let mut req = request("%%>message:1:1095112795:call.execute::called=alice");
+
+        assert!(!block_on(router().route(&mut req)).unwrap());
+        assert_eq!(req.retvalue, "");
+    }
+
+    #[test]
+    fn it_routes_in_order() {
+        let mut req = request("%%>message:1:1095112795:call.route::called=bob");
+
+        assert!(block_on(router().route(&mut req)).unwrap());
+        assert_eq!(req.retvalue, "sip/voicemail");
+        assert_eq!(
+            router().names().into_iter().collect::<Vec<_>>(),
+            ["call.route", "engine.timer"]
+        );
+    }
+}
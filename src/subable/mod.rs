@@ -0,0 +1,110 @@
+//! A low-level asynchronous subscription mechanism over a [`TryStream`],
+//! routing it's items to the subscriber of their [`Topic`].
+//!
+//! A [`Topic`] may be subscribed to multiple times, it's items are then delivered to
+//! exactly one of the subscribers, the _oldest_ one still subscribed, so that
+//! concurrent requests waiting on the same topic are answered in order.
+//! A subscriber may also filter the items of it's topic with [`Subable::subscribe_filter`],
+//! the items it rejects being delivered to the next subscribers as if it wasn't subscribed.
+//!
+//! Items destined to another subscriber than the one polling are staged aside, so that
+//! the subscriber polling can proceed with the next items, up to [`STAGING_CAPACITY`]
+//! items per subscriber: past this capacity, the polling subscriber waits for the other one to consume them.
+//! The items staged for a subscriber that is dropped before consuming them are yielded as [`Item::Unhandled`].
+//!
+//! ## Delivery
+//!
+//! An item destined to a subscriber is never dropped while it's subscribed, however long it takes
+//! to poll it's stream: only the items staged for a subscriber that is dropped
+//! or unsubscribed before consuming them are yielded as [`Item::Unhandled`].
+//!
+//! ## Fairness
+//!
+//! Each topic's items are delivered in the order of the stream, and the items staged for a
+//! subscriber are always yielded before it drives the stream further. A burst of items
+//! aimed at one subscriber thus delays the items of another one by at most [`STAGING_CAPACITY`]
+//! items, and only while the burst's subscriber doesn't consume them: a subscriber that
+//! stops polling while still subscribed holds the others back once it's staging is full.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use futures::{TryStream, stream::Peekable, task::AtomicWaker};
+
+#[cfg(test)]
+mod tests;
+
+mod subed;
+pub use subed::{Item, Subed};
+
+mod sub;
+pub use sub::Subable;
+
+/// A predicate on the items delivered to a subscriber, see [`Subable::subscribe_filter`].
+type Filter<I> = Arc<dyn Fn(&I) -> bool + Send + Sync>;
+
+/// A subscriber of a [`Topic`], with it's waker and optional filter.
+struct Subscriber<I> {
+    id: u64,
+    waker: Arc<AtomicWaker>,
+    filter: Option<Filter<I>>,
+
+    /// Whether the subscriber is waiting for another one to consume it's staged items.
+    blocked: AtomicBool,
+}
+
+impl<I> Subscriber<I> {
+    /// Whether the subscriber accepts to be delivered the `item`.
+    fn accepts(&self, item: &I) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(item))
+    }
+
+    /// Wake the subscriber if it's waiting for another one to consume it's staged items.
+    fn unblock(&self) {
+        if self.blocked.swap(false, Ordering::Relaxed) {
+            self.waker.wake();
+        }
+    }
+}
+
+/// The subscribers of a [`Topic`], ordered by subscription.
+type Subscribers<I> = Vec<Subscriber<I>>;
+
+/// The count of items staged per subscriber, before applying backpressure on the stream.
+pub const STAGING_CAPACITY: usize = 16;
+
+struct State<S: TryStream> {
+    stream: Peekable<S>,
+
+    /// The items staged aside, by subscriber identifier.
+    staged: HashMap<u64, VecDeque<S::Ok>>,
+}
+
+struct Inner<S: TryStream, T: Topic> {
+    /// The subscribers by topic, only ever locked after the `state` when both are held.
+    wakers: RwLock<HashMap<T, Subscribers<T::Item>>>,
+    ids: AtomicU64,
+    state: Mutex<State<S>>,
+}
+
+/// The _topic_ that will be used to route items to a specific subscriber.
+pub trait Topic: Debug + Clone + Hash + Eq {
+    /// The type of the items in the [`Subable`] stream.
+    type Item;
+
+    /// Identify the topic from the item type.
+    fn topic(item: &Self::Item) -> Self;
+
+    /// Fallback for when the item is _unhandled_, you might want to change it's [`Topic`] based on
+    /// this knowledge.
+    fn fallback(self) -> Self {
+        self
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, atomic::Ordering},
+    task::Poll,
+};
+
+use futures::{Stream, StreamExt, TryStream};
+
+use super::{Inner, State, Subed, Subscriber, Topic};
+
+/// A _stream_ that can be [`Subable::subscribe`]d to.
+pub struct Subable<S: TryStream, T: Topic> {
+    inner: Arc<Inner<S, T>>,
+}
+
+impl<S: TryStream, T: Topic> Subable<S, T> {
+    /// Create a new _subable_ from a `stream`.
+    pub fn new(stream: S) -> Self {
+        Self {
+            inner: Inner {
+                wakers: Default::default(),
+                ids: Default::default(),
+                state: State {
+                    stream: stream.peekable(),
+                    staged: Default::default(),
+                }
+                .into(),
+            }
+            .into(),
+        }
+    }
+
+    /// Subscribe to the provided [`Topic`], queuing behind
+    /// the previous subscribers of the same [`Topic`] if any.
+    pub fn subscribe(&self, topic: T) -> Subed<S, T> {
+        self.insert(topic, None)
+    }
+
+    /// Subscribe to the provided [`Topic`] like [`Self::subscribe`], only to the items
+    /// satisfying the `predicate`, the others being delivered as if not subscribed.
+    pub fn subscribe_filter(
+        &self,
+        topic: T,
+        predicate: impl Fn(&T::Item) -> bool + Send + Sync + 'static,
+    ) -> Subed<S, T> {
+        self.insert(topic, Some(Arc::new(predicate)))
+    }
+
+    fn insert(&self, topic: T, filter: Option<super::Filter<T::Item>>) -> Subed<S, T> {
+        let id = self.inner.ids.fetch_add(1, Ordering::Relaxed);
+
+        self.inner
+            .wakers
+            .write()
+            .unwrap()
+            .entry(topic.clone())
+            .or_default()
+            .push(Subscriber {
+                id,
+                waker: Default::default(),
+                filter,
+                blocked: Default::default(),
+            });
+
+        tracing::trace!("subscribing {topic:?} ({id})");
+
+        Subed::new(self.inner.clone(), topic, id)
+    }
+
+    /// Unsubscribe all the subscribers of the [`Topic`]s satisfying the `predicate`,
+    /// triggering their streams to return [`None`] once they yielded the items already staged for them.
+    pub fn unsubscribe_matching(&self, predicate: impl Fn(&T) -> bool) {
+        let mut wakers = self.inner.wakers.write().unwrap();
+        let topics = wakers
+            .keys()
+            .filter(|topic| predicate(topic))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for topic in topics {
+            tracing::trace!("unsubscribing all of {topic:?}");
+
+            for subscriber in wakers.remove(&topic).into_iter().flatten() {
+                subscriber.waker.wake();
+            }
+        }
+
+        // The items staged for them are now orphaned, wake the tasks waiting for them to be consumed
+        for subscriber in wakers.values().flatten() {
+            subscriber.unblock();
+        }
+    }
+
+    /// Whether the provided [`Topic`] currently has a subscriber.
+    pub fn is_subscribed(&self, topic: &T) -> bool {
+        self.inner.wakers.read().unwrap().contains_key(topic)
+    }
+
+    /// The count of topics currently having a subscriber.
+    pub fn active_topics(&self) -> usize {
+        self.inner.wakers.read().unwrap().len()
+    }
+
+    /// Unsubscribe all currently subscribed [`Subed`]
+    /// triggering individual streams to return [`None`],
+    /// once they yielded the items already staged for them.
+    pub fn unsubscribe_all(&self) {
+        for (_, wakers) in self.inner.wakers.write().unwrap().drain() {
+            // Wake all tasks, that will subsequently return `None`
+            for subscriber in wakers {
+                subscriber.waker.wake();
+            }
+        }
+    }
+}
+
+impl<S: TryStream + Stream<Item = Result<S::Ok, S::Error>> + Unpin, T: Topic> Subable<S, T> {
+    /// Wait for the next item of the stream without consuming it,
+    /// returning whether the stream ended or errored instead of yielding an item.
+    pub async fn peek_ended(&self) -> bool {
+        // Wake the subscribers once done or dropped, as this task may have taken over their wake-up
+        let _wake = WakeOnDrop(&self.inner);
+
+        futures::future::poll_fn(|cx| {
+            let mut state = self.inner.state.lock().unwrap();
+
+            match Pin::new(&mut state.stream).poll_peek(cx) {
+                Poll::Ready(Some(Ok(_))) => Poll::Ready(false),
+                Poll::Ready(_) => Poll::Ready(true),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+/// Wake all the subscribers of the [`Inner`] when dropped.
+struct WakeOnDrop<'i, S: TryStream, T: Topic>(&'i Inner<S, T>);
+
+impl<S: TryStream, T: Topic> Drop for WakeOnDrop<'_, S, T> {
+    fn drop(&mut self) {
+        for subscriber in self.0.wakers.read().unwrap().values().flatten() {
+            subscriber.waker.wake();
+        }
+    }
+}
+
+impl<S: TryStream, T: Topic> Drop for Subable<S, T> {
+    fn drop(&mut self) {
+        self.unsubscribe_all();
+    }
+}
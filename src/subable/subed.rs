@@ -0,0 +1,231 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, atomic::Ordering},
+};
+
+use futures::{Stream, TryStream, task};
+
+use super::{Inner, STAGING_CAPACITY, State, Topic};
+
+/// A yielded item from a _subscription_.
+pub enum Item<I> {
+    /// The item has been subscribed to.
+    Subscribed(I),
+
+    /// The item is not handled by any subscriber.
+    Unhandled(I),
+}
+
+/// A _subscription_ to a [`Topic`] yielding this topic's items.
+pub struct Subed<S: TryStream, T: Topic> {
+    inner: Arc<Inner<S, T>>,
+    topic: T,
+    id: u64,
+}
+
+impl<S: TryStream, T: Topic> Subed<S, T> {
+    pub(super) fn new(inner: Arc<Inner<S, T>>, topic: T, id: u64) -> Self {
+        Self { inner, topic, id }
+    }
+}
+
+impl<S: TryStream, T: Topic> Drop for Subed<S, T> {
+    fn drop(&mut self) {
+        tracing::trace!("unsubscribing {:?} ({})", self.topic, self.id);
+
+        let mut wakers = self.inner.wakers.write().unwrap();
+        if let Some(subscribers) = wakers.get_mut(&self.topic) {
+            subscribers.retain(|subscriber| subscriber.id != self.id);
+
+            if subscribers.is_empty() {
+                wakers.remove(&self.topic);
+            }
+        }
+
+        // Wake the next subscriber of the topic, that the items of this one are now destined to,
+        // or any other to take over driving the stream
+        if let Some(subscriber) = wakers
+            .get(&self.topic)
+            .and_then(|subscribers| subscribers.first())
+            .or_else(|| wakers.values().flatten().next())
+        {
+            subscriber.waker.wake();
+        }
+
+        // The items staged for this one are now orphaned, wake the tasks waiting for them to be consumed
+        for subscriber in wakers.values().flatten() {
+            subscriber.unblock();
+        }
+    }
+}
+
+impl<S: TryStream + Stream<Item = Result<S::Ok, S::Error>> + Unpin, T: Topic<Item = S::Ok>> Stream
+    for Subed<S, T>
+{
+    type Item = Result<Item<S::Ok>, S::Error>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        // The `wakers` are always locked after the `state`, so their guard
+        // is released here before the `state` is locked below
+        let subscribed =
+            match self
+                .inner
+                .wakers
+                .read()
+                .unwrap()
+                .get(&self.topic)
+                .and_then(|subscribers| {
+                    subscribers
+                        .iter()
+                        .find(|subscriber| subscriber.id == self.id)
+                }) {
+                // Register the task for wake-up
+                Some(subscriber) => {
+                    subscriber.waker.register(cx.waker());
+
+                    true
+                }
+                None => false,
+            };
+
+        // If the waker isn't registered, that means the stream is closed,
+        // only the items already staged for this subscriber are yielded
+        if !subscribed {
+            let mut state = self.inner.state.lock().unwrap();
+
+            return task::Poll::Ready(
+                unstage(&mut state.staged, self.id).map(|item| Ok(Item::Subscribed(item))),
+            );
+        }
+
+        // A blocking lock is used, as polling the stream never blocks, and a pending
+        // asynchronous lock would lose it's wake-up when dropped at the end of this poll
+        let mut state = self.inner.state.lock().unwrap();
+        let State { stream, staged } = &mut *state;
+        let mut stream = std::pin::Pin::new(stream);
+
+        let wakers = self.inner.wakers.read().unwrap();
+
+        // The item is destined to the oldest subscriber of the topic accepting it
+        let destined = |item: &S::Ok| {
+            let topic = T::topic(item);
+            let find = |topic: &T| {
+                wakers
+                    .get(topic)?
+                    .iter()
+                    .find(|subscriber| subscriber.accepts(item))
+            };
+
+            find(&topic).or_else(|| find(&topic.fallback()))
+        };
+
+        // Wake the tasks waiting for items staged to be consumed
+        let unblock = || {
+            for subscriber in wakers.values().flatten() {
+                subscriber.unblock();
+            }
+        };
+
+        // The items staged for this task come first
+        if let Some(item) = unstage(staged, self.id) {
+            unblock();
+            return task::Poll::Ready(Some(Ok(Item::Subscribed(item))));
+        }
+
+        // The items staged for tasks that are gone are unhandled
+        if let Some(id) = staged
+            .keys()
+            .find(|id| {
+                !wakers
+                    .values()
+                    .flatten()
+                    .any(|subscriber| subscriber.id == **id)
+            })
+            .copied()
+            && let Some(item) = unstage(staged, id)
+        {
+            unblock();
+            return task::Poll::Ready(Some(Ok(Item::Unhandled(item))));
+        }
+
+        let polled = loop {
+            match futures::ready!(stream.as_mut().poll_peek(cx)) {
+                Some(Ok(item)) => match destined(item) {
+                    // The item is subscribed, pop it as `Subscribed`
+                    Some(subscriber) if subscriber.id == self.id => {
+                        break stream.as_mut().poll_next(cx).map_ok(Item::Subscribed);
+                    }
+                    Some(subscriber) => {
+                        // The item is destined to another task, wake it
+                        subscriber.waker.wake();
+
+                        let queue = staged.entry(subscriber.id).or_default();
+                        if queue.len() >= STAGING_CAPACITY {
+                            // The staging is full, stay pending until the other task consumes
+                            if let Some(this) = wakers
+                                .get(&self.topic)
+                                .into_iter()
+                                .flatten()
+                                .find(|subscriber| subscriber.id == self.id)
+                            {
+                                this.blocked.store(true, Ordering::Relaxed);
+                            }
+
+                            return task::Poll::Pending;
+                        }
+
+                        // Stage the item aside and look at the next one
+                        match stream.as_mut().poll_next(cx) {
+                            task::Poll::Ready(Some(Ok(item))) => queue.push_back(item),
+                            _ => unreachable!("the item was successfully peeked"),
+                        }
+                    }
+                    // The item is unhandled, pop it as `Unhandled`
+                    None => break stream.as_mut().poll_next(cx).map_ok(Item::Unhandled),
+                },
+
+                // The stream errored, pop it from the stream
+                Some(_) => break stream.as_mut().poll_next(cx).map_ok(Item::Unhandled),
+
+                // The stream ended, return `None`
+                None => return task::Poll::Ready(None),
+            }
+        };
+
+        // Wake the subscriber of the next item, so that it keeps driving the stream in case
+        // this task isn't polled anymore, or stay registered for it's wake-up
+        match stream.as_mut().poll_peek(cx) {
+            task::Poll::Ready(Some(Ok(item))) => {
+                if let Some(subscriber) = destined(item)
+                    && subscriber.id != self.id
+                {
+                    subscriber.waker.wake();
+                }
+            }
+            // The stream ended or errored, every subscriber has to observe it
+            task::Poll::Ready(_) => {
+                for subscriber in wakers.values().flatten() {
+                    subscriber.waker.wake();
+                }
+            }
+            task::Poll::Pending => (),
+        }
+
+        polled
+    }
+}
+
+/// Pop the next item staged for the subscriber `id`, forgetting the subscriber once empty.
+fn unstage<I>(staged: &mut HashMap<u64, VecDeque<I>>, id: u64) -> Option<I> {
+    let queue = staged.get_mut(&id)?;
+    let item = queue.pop_front();
+
+    if queue.is_empty() {
+        staged.remove(&id);
+    }
+
+    item
+}
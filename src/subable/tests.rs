@@ -0,0 +1,327 @@
+use std::{
+    convert::Infallible,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Context,
+};
+
+use futures::{
+    FutureExt, Stream, StreamExt,
+    channel::mpsc,
+    stream,
+    task::{self, ArcWake},
+};
+
+use super::*;
+
+impl Topic for char {
+    type Item = (char, u32);
+
+    fn topic(item: &Self::Item) -> Self {
+        item.0
+    }
+}
+
+type Items = stream::Iter<std::vec::IntoIter<Result<(char, u32), Infallible>>>;
+
+fn subable(items: impl IntoIterator<Item = (char, u32)>) -> Subable<Items, char> {
+    Subable::new(stream::iter(items.into_iter().map(Ok).collect::<Vec<_>>()))
+}
+
+fn next(sub: &mut Subed<Items, char>) -> Option<Option<(bool, u32)>> {
+    sub.next().now_or_never().map(|item| {
+        item.map(|item| match item.unwrap() {
+            Item::Subscribed((_, n)) => (true, n),
+            Item::Unhandled((_, n)) => (false, n),
+        })
+    })
+}
+
+#[test]
+fn it_stages_interleaved_topics() {
+    let subable = subable([('b', 1), ('a', 1), ('b', 2), ('c', 1), ('a', 2)]);
+    let (mut a, mut b) = (subable.subscribe('a'), subable.subscribe('b'));
+
+    assert_eq!(next(&mut a), Some(Some((true, 1))));
+    assert_eq!(next(&mut a), Some(Some((false, 1))));
+    assert_eq!(next(&mut a), Some(Some((true, 2))));
+    assert_eq!(next(&mut a), Some(None));
+
+    assert_eq!(next(&mut b), Some(Some((true, 1))));
+    assert_eq!(next(&mut b), Some(Some((true, 2))));
+    assert_eq!(next(&mut b), Some(None));
+}
+
+#[test]
+fn it_applies_backpressure() {
+    let subable = subable(
+        (0..=STAGING_CAPACITY as u32)
+            .map(|n| ('b', n))
+            .chain([('a', 0)]),
+    );
+    let (mut a, mut b) = (subable.subscribe('a'), subable.subscribe('b'));
+
+    assert_eq!(next(&mut a), None);
+    assert_eq!(next(&mut b), Some(Some((true, 0))));
+    assert_eq!(next(&mut a), Some(Some((true, 0))));
+}
+
+#[test]
+fn it_unhandles_orphaned_items() {
+    let subable = subable([('b', 1), ('a', 1)]);
+    let (mut a, b) = (subable.subscribe('a'), subable.subscribe('b'));
+
+    assert_eq!(next(&mut a), Some(Some((true, 1))));
+    drop(b);
+    assert_eq!(next(&mut a), Some(Some((false, 1))));
+}
+
+#[test]
+fn it_filters_items() {
+    let shared = subable([('a', 1), ('a', 2), ('a', 3)]);
+    let (mut odd, mut any) = (
+        shared.subscribe_filter('a', |(_, n)| n % 2 == 1),
+        shared.subscribe('a'),
+    );
+
+    assert_eq!(next(&mut odd), Some(Some((true, 1))));
+    assert_eq!(next(&mut odd), Some(Some((true, 3))));
+    assert_eq!(next(&mut any), Some(Some((true, 2))));
+
+    let rejecting = subable([('a', 1), ('a', 2)]);
+    let mut even = rejecting.subscribe_filter('a', |(_, n)| n % 2 == 0);
+
+    assert_eq!(next(&mut even), Some(Some((false, 1))));
+    assert_eq!(next(&mut even), Some(Some((true, 2))));
+}
+
+#[test]
+fn it_tracks_subscriptions() {
+    let subable = subable([('a', 1)]);
+    assert_eq!(subable.active_topics(), 0);
+
+    let (a, a2, mut b, mut c) = (
+        subable.subscribe('a'),
+        subable.subscribe('a'),
+        subable.subscribe('b'),
+        subable.subscribe('c'),
+    );
+    assert_eq!(subable.active_topics(), 3);
+    assert!(subable.is_subscribed(&'a'));
+    assert!(!subable.is_subscribed(&'d'));
+
+    subable.unsubscribe_matching(|topic| *topic != 'a');
+    assert_eq!(next(&mut b), Some(None));
+    assert_eq!(next(&mut c), Some(None));
+    assert!(!subable.is_subscribed(&'b'));
+    assert!(!subable.is_subscribed(&'c'));
+    assert_eq!(subable.active_topics(), 1);
+
+    drop(a);
+    assert_eq!(subable.active_topics(), 1);
+    drop(a2);
+    assert_eq!(subable.active_topics(), 0);
+
+    drop((b, c));
+    assert_eq!(subable.active_topics(), 0);
+}
+
+/// Bursts of each topic in turn, interleaved with a trickle of the other topics.
+fn interleaved_bursts() -> Vec<(char, u32)> {
+    const BURST: u32 = STAGING_CAPACITY as u32 * 3;
+
+    let mut counts = HashMap::<char, u32>::new();
+    let mut items = Vec::new();
+    for burst in ['a', 'b', 'c'] {
+        for n in 0..BURST {
+            let topics = if n % 4 == 0 {
+                &['a', 'b', 'c'][..]
+            } else {
+                &[burst]
+            };
+
+            for &topic in topics {
+                let count = counts.entry(topic).or_default();
+                items.push((topic, *count));
+                *count += 1;
+            }
+        }
+    }
+
+    items
+}
+
+/// Assert that each topic `received` all of it's `items`, in order.
+fn assert_drained(items: &[(char, u32)], [a, b, c]: [Vec<(char, u32)>; 3]) {
+    for (topic, received) in [('a', a), ('b', b), ('c', c)] {
+        let expected = items
+            .iter()
+            .filter(|item| item.0 == topic)
+            .copied()
+            .collect::<Vec<_>>();
+
+        assert_eq!(received, expected, "topic {topic:?} drained out of order");
+    }
+}
+
+#[test]
+fn it_drains_interleaved_bursts() {
+    let items = interleaved_bursts();
+    let subable = subable(items.clone());
+    let collect = |topic| {
+        subable
+            .subscribe(topic)
+            .map(|item| match item.unwrap() {
+                Item::Subscribed(item) => item,
+                Item::Unhandled(item) => panic!("unexpected unhandled item {item:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let (a, b, c) = futures::executor::block_on(futures::future::join3(
+        collect('a'),
+        collect('b'),
+        collect('c'),
+    ));
+
+    assert_drained(&items, [a, b, c]);
+}
+
+/// Yield to the executor once, waking the task to be polled again.
+async fn yield_now() {
+    let mut yielded = false;
+
+    futures::future::poll_fn(|cx| {
+        if std::mem::replace(&mut yielded, true) {
+            task::Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            task::Poll::Pending
+        }
+    })
+    .await
+}
+
+#[test]
+fn it_drains_interleaved_bursts_to_busy_subscribers() {
+    let items = interleaved_bursts();
+    let subable = subable(items.clone());
+
+    // Each subscriber is busy for a few polls between it's items, like awaiting a request
+    let collect = |topic, polls: usize| {
+        let mut sub = subable.subscribe(topic);
+
+        async move {
+            let mut received = Vec::new();
+            while let Some(item) = sub.next().await {
+                match item.unwrap() {
+                    Item::Subscribed(item) => received.push(item),
+                    Item::Unhandled(item) => panic!("unexpected unhandled item {item:?}"),
+                }
+
+                for _ in 0..polls {
+                    yield_now().await;
+                }
+            }
+
+            received
+        }
+    };
+
+    let (a, b, c) = futures::executor::block_on(futures::future::join3(
+        collect('a', 1),
+        collect('b', 3),
+        collect('c', 7),
+    ));
+
+    assert_drained(&items, [a, b, c]);
+}
+
+/// A waker counting it's wake-ups.
+#[derive(Default)]
+struct Wakes(AtomicUsize);
+
+impl Wakes {
+    fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl ArcWake for Wakes {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Poll the pending `sub` once with a counting waker, returning the waker's counter.
+fn register<S>(sub: &mut Subed<S, char>) -> Arc<Wakes>
+where
+    S: TryStream<Ok = (char, u32)> + Stream<Item = Result<S::Ok, S::Error>> + Unpin,
+{
+    let wakes = Arc::new(Wakes::default());
+    let waker = task::waker(wakes.clone());
+
+    assert!(
+        sub.poll_next_unpin(&mut Context::from_waker(&waker))
+            .is_pending()
+    );
+
+    wakes
+}
+
+#[test]
+fn it_wakes_subscribers_after_a_dropped_peek() {
+    let (_tx, rx) = mpsc::unbounded::<Result<(char, u32), Infallible>>();
+    let subable = Subable::new(rx);
+    let mut sub = subable.subscribe('a');
+
+    let wakes = register(&mut sub);
+
+    // The peek takes over the wake-up of the subscriber, and is given up before the stream yields
+    let mut peek = subable.peek_ended().boxed_local();
+    assert!((&mut peek).now_or_never().is_none());
+    assert_eq!(wakes.count(), 0);
+
+    drop(peek);
+    assert_eq!(wakes.count(), 1);
+}
+
+#[test]
+fn it_wakes_the_subscriber_of_the_next_item() {
+    let (tx, rx) = mpsc::unbounded::<Result<(char, u32), Infallible>>();
+    let subable = Subable::new(rx);
+    let (mut a, mut b, mut c) = (
+        subable.subscribe('a'),
+        subable.subscribe('b'),
+        subable.subscribe('c'),
+    );
+
+    let wakes = [&mut a, &mut b, &mut c].map(register);
+
+    tx.unbounded_send(Ok(('a', 1))).unwrap();
+    tx.unbounded_send(Ok(('b', 1))).unwrap();
+    let before = wakes.each_ref().map(|wakes| wakes.count());
+
+    assert!(matches!(
+        a.next().now_or_never(),
+        Some(Some(Ok(Item::Subscribed(('a', 1)))))
+    ));
+
+    // Only the subscriber of the next item is woken, rather than all of them
+    assert_eq!(wakes[1].count(), before[1] + 1);
+    assert_eq!(wakes[2].count(), before[2]);
+}
+
+#[test]
+fn it_unblocks_subscribers_on_consumption() {
+    let subable = subable(
+        (0..=STAGING_CAPACITY as u32)
+            .map(|n| ('b', n))
+            .chain([('a', 0)]),
+    );
+    let (mut a, mut b) = (subable.subscribe('a'), subable.subscribe('b'));
+
+    let wakes = register(&mut a);
+
+    assert_eq!(next(&mut b), Some(Some((true, 0))));
+    assert_eq!(wakes.count(), 1);
+}
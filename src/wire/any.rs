@@ -0,0 +1,52 @@
+use super::*;
+
+macro_rules! messages {
+    ($($variant:ident => $tag:literal,)*) => {
+        /// Any of the known messages of the wire format, as classified by [`try_from_str_any`].
+        #[derive(Debug)]
+        pub enum AnyMessage {
+            /// An [`ErrorIn`] notification.
+            ErrorIn(ErrorIn),
+
+            $(
+                #[doc = concat!("A [`struct@", stringify!($variant), "`] message.")]
+                $variant($variant),
+            )*
+        }
+
+        /// Deserialize the `line` as whichever known message it's leading keyword tags it as,
+        /// to classify untrusted input without knowing it's type beforehand.
+        pub fn try_from_str_any(line: &str) -> Result<AnyMessage> {
+            if let Some(error) = ErrorIn::parse(line) {
+                return Ok(AnyMessage::ErrorIn(error));
+            }
+
+            let tag = line.split(':').next().unwrap_or_default();
+            match tag {
+                $($tag => from_str(line).map(AnyMessage::$variant),)*
+                _ if line.trim().is_empty() => Err(Error::EmptyInput),
+                _ => Err(Error::MismatchedTag { field: 0 }),
+            }
+        }
+    };
+}
+
+messages! {
+    Message => "%%>message",
+    MessageAck => "%%<message",
+    Install => "%%>install",
+    InstallAck => "%%<install",
+    Uninstall => "%%>uninstall",
+    UninstallAck => "%%<uninstall",
+    Watch => "%%>watch",
+    WatchAck => "%%<watch",
+    Unwatch => "%%>unwatch",
+    UnwatchAck => "%%<unwatch",
+    SetLocal => "%%>setlocal",
+    SetLocalAck => "%%<setlocal",
+    Connect => "%%>connect",
+    Output => "%%>output",
+    Debug => "%%>debug",
+    Quit => "%%>quit",
+    QuitAck => "%%<quit",
+}
@@ -0,0 +1,38 @@
+//! Wire-format specific attributes for the fields of custom message types.
+//!
+//! ```
+//! use yengine::wire::{self, attr};
+//!
+//! #[derive(Debug, facet::Facet)]
+//! #[facet(type_tag = "%%<query")]
+//! struct QueryAck {
+//!     name: String,
+//!
+//!     #[facet(default, attr::empty)]
+//!     value: Option<String>,
+//! }
+//!
+//! let ack = wire::from_str::<QueryAck>("%%<query:name:").unwrap();
+//! assert_eq!(ack.value.as_deref(), Some(""));
+//!
+//! let ack = wire::from_str::<QueryAck>("%%<query:name").unwrap();
+//! assert_eq!(ack.value, None);
+//! ```
+
+facet::define_attr_grammar! {
+    ns "wire";
+    crate_path crate::wire::attr;
+
+    /// The wire-format specific field attributes, in the `wire` namespace.
+    pub enum Attr {
+        /// Deserialize an empty part to `Some("")` rather than `None` for an [`Option`] field,
+        /// usually combined with `default` so that the field is only `None` when it's part is absent.
+        Empty,
+    }
+}
+
+#[doc(hidden)]
+pub use __attr;
+
+/// The namespace of the attributes, as found in the field's shape.
+pub(super) const NS: &str = "wire";
@@ -1,32 +1,103 @@
-use std::collections::VecDeque;
+use std::{
+    borrow::Cow,
+    collections::{HashSet, VecDeque},
+    time::{Duration, SystemTime},
+};
 
-use facet::{Def, Facet, Partial, Type, UserType};
+use facet::{Def, Facet, Opaque, Partial, Type, UserType};
 
 use super::{Error, Result};
 
 struct Deserializer<'de> {
     parts: VecDeque<&'de str>,
+    untagged: bool,
+    strict: bool,
+
+    /// Whether the next value is a field marked [`attr::empty`](super::attr::Attr::Empty).
+    empty: bool,
+
+    /// Index of the next colon-delimited field.
+    field: usize,
 }
 
 impl<'de> Deserializer<'de> {
+    fn pop(&mut self) -> Option<&'de str> {
+        let part = self.parts.pop_front()?;
+        self.field += 1;
+
+        Some(part)
+    }
+
+    /// A deserializer of the `part` nested in the current one, reported at the `field`.
+    fn scoped(&self, part: &'de str, field: usize) -> Self {
+        Self {
+            parts: VecDeque::from([part]),
+            untagged: false,
+            strict: self.strict,
+            empty: false,
+            field,
+        }
+    }
+
+    fn missing_value(&self) -> Error {
+        Error::MissingValue { field: self.field }
+    }
+
     fn deserialize_tag(&mut self) -> Result<&str> {
-        self.parts.pop_front().ok_or(Error::MissingTag)
+        self.pop().ok_or(Error::MissingTag)
+    }
+
+    fn deserialize_scalar(&mut self, partial: Partial<'de>) -> Result<Partial<'de>> {
+        let value = self.pop().ok_or_else(|| self.missing_value())?;
+        let value = super::upcode::decode(value)?;
+        let invalid = |expected| Error::InvalidValue {
+            field: self.field - 1,
+            expected,
+        };
+
+        let shape = partial.shape();
+        if shape.is_type::<bool>() {
+            let value = parse_bool(&value).ok_or_else(|| invalid("boolean"))?;
+
+            Ok(partial.set(value)?)
+        } else if shape.is_type::<Duration>() {
+            let ms = value.parse().map_err(|_| invalid("milliseconds"))?;
+
+            Ok(partial.set(Duration::from_millis(ms))?)
+        } else if shape.is_type::<Opaque<SystemTime>>() {
+            let time = value
+                .parse()
+                .ok()
+                .and_then(|secs| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+                .ok_or_else(|| invalid("unix timestamp"))?;
+
+            Ok(partial.set(Opaque(time))?)
+        } else {
+            Ok(partial.parse_from_str(&value)?)
+        }
     }
 
-    fn deserialize_scalar(&mut self, partial: Partial<'static>) -> Result<Partial<'static>> {
-        let value = self.parts.pop_front().ok_or(Error::MissingValue)?;
+    fn deserialize_borrowed(&mut self, partial: Partial<'de>) -> Result<Partial<'de>> {
+        let value = self.pop().ok_or_else(|| self.missing_value())?;
 
-        Ok(partial.parse_from_str(&super::upcode::decode(value)?)?)
+        match super::upcode::decode(value)? {
+            Cow::Borrowed(value) => Ok(partial.set(value)?),
+            Cow::Owned(_) => Err(Error::EscapedBorrow {
+                field: self.field - 1,
+            }),
+        }
     }
 
     fn deserialize_option(
         &mut self,
-        mut partial: Partial<'static>,
+        mut partial: Partial<'de>,
         has_default: bool,
-    ) -> Result<Partial<'static>> {
+        empty: bool,
+    ) -> Result<Partial<'de>> {
+        // An empty part signals `None`, unless the field is marked to keep it as `Some("")`
         match self.parts.front() {
-            Some(&"") => {
-                self.parts.pop_front();
+            Some(&"") if !empty => {
+                self.pop();
 
                 Ok(partial.set_default()?)
             }
@@ -36,32 +107,61 @@ impl<'de> Deserializer<'de> {
 
                 Ok(partial.end()?)
             }
-            None => Err(Error::MissingValue),
+            None => Err(self.missing_value()),
         }
     }
 
     fn deserialize_map(
         &mut self,
-        mut partial: Partial<'static>,
+        mut partial: Partial<'de>,
         has_default: bool,
-    ) -> Result<Partial<'static>> {
+    ) -> Result<Partial<'de>> {
         partial = partial.init_map()?;
 
-        self.parts = self
-            .parts
-            .drain(..)
-            .map(|kv| kv.split_once('=').ok_or(Error::MisformatedMap))
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .flat_map(|(k, v)| [k, v])
-            .collect();
+        let mut keys = HashSet::new();
+        while let Some(kv) = self.pop() {
+            let field = self.field - 1;
 
-        while self.parts.front().is_some() {
+            // Keys have their `=` escaped, so the first one is always the separator
+            let (k, v) = kv.split_once('=').ok_or_else(|| Error::MisformatedMap {
+                field,
+                token: kv.into(),
+            })?;
+
+            // The engine may repeat a parameter, the last value then wins unless strict
+            if self.strict {
+                let key = super::upcode::decode(k)?;
+                if !keys.insert(key.clone()) {
+                    return Err(Error::DuplicateKey(key.into_owned()));
+                }
+            }
+
+            // Deserialize the key and value in their own scope, each reported at the pair's field
             partial = partial.begin_key()?;
-            partial = self.deserialize_value(partial, has_default)?;
+            partial = self
+                .scoped(k, field)
+                .deserialize_value(partial, has_default)?;
             partial = partial.end()?;
 
             partial = partial.begin_value()?;
+            partial = self
+                .scoped(v, field)
+                .deserialize_value(partial, has_default)?;
+            partial = partial.end()?;
+        }
+
+        Ok(partial)
+    }
+
+    fn deserialize_list(
+        &mut self,
+        mut partial: Partial<'de>,
+        has_default: bool,
+    ) -> Result<Partial<'de>> {
+        partial = partial.init_list()?;
+
+        while self.parts.front().is_some() {
+            partial = partial.begin_list_item()?;
             partial = self.deserialize_value(partial, has_default)?;
             partial = partial.end()?;
         }
@@ -69,21 +169,29 @@ impl<'de> Deserializer<'de> {
         Ok(partial)
     }
 
-    fn deserialize_enum(&mut self, partial: Partial<'static>) -> Result<Partial<'static>> {
-        let value = self.parts.pop_front().ok_or(Error::MissingValue)?;
+    fn deserialize_enum(&mut self, partial: Partial<'de>) -> Result<Partial<'de>> {
+        let value = self.pop().ok_or_else(|| self.missing_value())?;
 
         Ok(partial.select_variant_named(value)?)
     }
 
     fn deserialize_value(
         &mut self,
-        mut partial: Partial<'static>,
+        mut partial: Partial<'de>,
         has_default: bool,
-    ) -> Result<Partial<'static>> {
-        if let Some(tag) = partial.shape().type_tag
-            && tag != self.deserialize_tag()?
-        {
-            return Err(Error::MismatchedTag);
+    ) -> Result<Partial<'de>> {
+        // The attribute only applies to the field itself, not to it's inner values
+        let empty = std::mem::take(&mut self.empty);
+
+        if let Some(tag) = partial.shape().type_tag {
+            let field = self.field;
+
+            if self.untagged {
+                // Only skip the top-level tag, once
+                self.untagged = false;
+            } else if tag != self.deserialize_tag()? {
+                return Err(Error::MismatchedTag { field });
+            }
         }
 
         if self.parts.front().is_none() && has_default {
@@ -91,9 +199,14 @@ impl<'de> Deserializer<'de> {
         }
 
         match partial.shape().ty {
+            _ if partial.shape().is_type::<&str>() => self.deserialize_borrowed(partial),
+            _ if partial.shape().is_type::<Opaque<SystemTime>>() => {
+                self.deserialize_scalar(partial)
+            }
             Type::User(UserType::Struct(st)) => {
                 for (idx, field) in st.fields.iter().enumerate() {
                     partial = partial.begin_nth_field(idx)?;
+                    self.empty = field.has_attr(Some(super::attr::NS), "empty");
                     partial =
                         self.deserialize_value(partial, has_default || field.has_default())?;
                     partial = partial.end()?;
@@ -104,27 +217,91 @@ impl<'de> Deserializer<'de> {
             Type::User(UserType::Enum(_)) => self.deserialize_enum(partial),
             _ => match partial.shape().def {
                 Def::Scalar => self.deserialize_scalar(partial),
-                Def::Option(_) => self.deserialize_option(partial, has_default),
+                Def::Option(_) => self.deserialize_option(partial, has_default, empty),
                 Def::Map(_) => self.deserialize_map(partial, has_default),
+                Def::List(_) => self.deserialize_list(partial, has_default),
 
-                _ => panic!("unable to deserialize type: {}", partial.shape()),
+                _ => Err(Error::UnsupportedType(partial.shape().to_string())),
             },
         }
     }
 
-    fn deserialize<T: facet::Facet<'static>>(mut self, partial: Partial<'static>) -> Result<T> {
-        self.deserialize_value(partial, Default::default())?
-            .build()?
-            .materialize()
-            .map_err(Into::into)
+    fn deserialize<T: Facet<'de>>(mut self, partial: Partial<'de>) -> Result<T> {
+        let partial = self.deserialize_value(partial, Default::default())?;
+
+        if self.strict && !self.parts.is_empty() {
+            return Err(Error::TrailingFields { field: self.field });
+        }
+
+        partial.build()?.materialize().map_err(Into::into)
     }
 }
 
+/// Parse a boolean from it's Yate textual representation,
+/// accepting `true`/`yes`/`on`/`enable`/`t`/`1` and `false`/`no`/`off`/`disable`/`f`/`0`.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" | "enable" | "t" | "1" => Some(true),
+        "false" | "no" | "off" | "disable" | "f" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Split the `input` in it's colon-delimited parts, sized up front for long lines.
+fn split(input: &str) -> VecDeque<&str> {
+    let mut parts =
+        VecDeque::with_capacity(memchr::memchr_iter(b':', input.as_bytes()).count() + 1);
+    parts.extend(input.split(':'));
+
+    parts
+}
+
 /// Deserialize an instance of `T` from it's textual representation.
-pub fn from_str<T: Facet<'static>>(input: &str) -> Result<T> {
+///
+/// `&str` fields borrow from the `input` as long as their value is not `%`-encoded.
+///
+/// The fields trailing the ones of `T` are ignored, so that the fields appended
+/// to a message by newer versions of the engine don't break it's deserialization,
+/// and a key repeated in a map keeps it's last value,
+/// use [`from_str_strict`] to reject them instead.
+pub fn from_str<'de, T: Facet<'de>>(input: &'de str) -> Result<T> {
+    deserialize(input, false)
+}
+
+/// Deserialize an instance of `T` from it's textual representation, like [`from_str`],
+/// but failing with [`Error::TrailingFields`] if fields trail the ones of `T`,
+/// and with [`Error::DuplicateKey`] if a key is repeated in a map.
+pub fn from_str_strict<'de, T: Facet<'de>>(input: &'de str) -> Result<T> {
+    deserialize(input, true)
+}
+
+fn deserialize<'de, T: Facet<'de>>(input: &'de str, strict: bool) -> Result<T> {
+    if input.trim().is_empty() {
+        return Err(Error::EmptyInput);
+    }
+
+    let partial = Partial::alloc::<T>()?;
+    let de = Deserializer {
+        parts: split(input),
+        untagged: false,
+        strict,
+        empty: false,
+        field: 0,
+    };
+
+    de.deserialize(partial)
+}
+
+/// Deserialize an instance of `T` from it's textual representation,
+/// not expecting the leading keyword tag, as produced by [`to_string_fields`](super::to_string_fields).
+pub fn from_str_fields<'de, T: Facet<'de>>(input: &'de str) -> Result<T> {
     let partial = Partial::alloc::<T>()?;
     let de = Deserializer {
-        parts: input.split(":").collect(),
+        parts: split(input),
+        untagged: true,
+        strict: false,
+        empty: false,
+        field: 0,
     };
 
     de.deserialize(partial)
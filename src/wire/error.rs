@@ -14,19 +14,81 @@ pub enum Error {
     #[error("unable to decode string: {0}")]
     Encoding(#[from] crate::wire::upcode::DecodeError),
 
+    /// The input was empty, or only made of whitespace.
+    #[error("empty input")]
+    EmptyInput,
+
     /// The message didn't include a tag.
     #[error("no message tag found")]
     MissingTag,
 
     /// The message didn't include required tag.
-    #[error("message tag didn't match struct tag")]
-    MismatchedTag,
+    #[error("message tag didn't match struct tag at field {field}")]
+    MismatchedTag {
+        /// Index of the colon-delimited field holding the tag.
+        field: usize,
+    },
 
     /// The message didn't include the required value.
-    #[error("expected value, but input is exhausted")]
-    MissingValue,
+    #[error("expected value at field {field}, but input is exhausted")]
+    MissingValue {
+        /// Index of the expected colon-delimited field.
+        field: usize,
+    },
 
     /// The format of the map wasn't respected.
-    #[error("expected a format with <key>=<value>")]
-    MisformatedMap,
+    #[error("expected a format with <key>=<value> at field {field}, got `{token}`")]
+    MisformatedMap {
+        /// Index of the offending colon-delimited field.
+        field: usize,
+
+        /// The offending token.
+        token: String,
+    },
+
+    /// The value couldn't be parsed as the expected type.
+    #[error("invalid {expected} at field {field}")]
+    InvalidValue {
+        /// Index of the offending colon-delimited field.
+        field: usize,
+
+        /// Description of the expected type.
+        expected: &'static str,
+    },
+
+    /// The value of a borrowed field was `%`-encoded, and can't be borrowed from the input.
+    #[error("value at field {field} is `%`-encoded and can't be borrowed")]
+    EscapedBorrow {
+        /// Index of the offending colon-delimited field.
+        field: usize,
+    },
+
+    /// The type can't be represented in the wire format.
+    #[error("unable to (de)serialize type `{0}`")]
+    UnsupportedType(String),
+
+    /// The debug level was out of the `0..=10` range.
+    #[error("debug level {0} is out of the 0..=10 range")]
+    InvalidDebugLevel(u8),
+
+    /// The message included fields past the expected ones, with [`from_str_strict`](super::from_str_strict).
+    #[error("unexpected trailing fields from field {field}")]
+    TrailingFields {
+        /// Index of the first trailing colon-delimited field.
+        field: usize,
+    },
+
+    /// The map contained the same key multiple times, with [`from_str_strict`](super::from_str_strict).
+    #[error("duplicate key `{0}` in map")]
+    DuplicateKey(String),
+
+    /// The message didn't serialize back to the line it was deserialized from.
+    #[error("`{input}` was re-serialized as `{output}`")]
+    RoundTrip {
+        /// The line the message was deserialized from.
+        input: String,
+
+        /// The line the message was serialized to.
+        output: String,
+    },
 }
@@ -7,8 +7,25 @@
 //! - **(>)**: _Application_ to _Engine_
 //! - **(<)**: _Engine_ to _Application_
 //! - **(~)**: _Bi_-directional
+//!
+//! ## Optional fields
+//!
+//! An [`Option`] field is `None` when it's part is empty, following the engine's
+//! convention for unset values, and a field marked `#[facet(default)]` may also be omitted altogether.
+//!
+//! Where an empty value is meaningful, the field is marked with the [`attr::empty`](attr::Attr::Empty)
+//! attribute to deserialize an empty part to `Some("")`, and is then only `None` when it's part is absent.
+//!
+//! ## Trailing fields and repeated keys
+//!
+//! The fields trailing the known ones of a message are ignored by [`from_str`],
+//! so that the fields appended by newer versions of the engine are tolerated,
+//! while [`from_str_strict`] rejects them with [`Error::TrailingFields`].
+//!
+//! Likewise, the engine may repeat a key in the parameters of a message:
+//! [`from_str`] keeps it's last value, while [`from_str_strict`] rejects it with [`Error::DuplicateKey`].
 
-use std::collections::BTreeMap;
+use std::time::SystemTime;
 
 #[cfg(test)]
 mod tests;
@@ -24,11 +41,49 @@ pub use de::*;
 mod ser;
 pub use ser::*;
 
+mod name;
+pub use name::MessageName;
+
+mod any;
+pub use any::{AnyMessage, try_from_str_any};
+
+pub mod attr;
+
+pub mod params;
+pub use params::Params;
+
+/// A command the engine doesn't answer with an acknowledgement,
+/// which may then be sent in batches, see [`Engine::send_batch`](crate::Engine::send_batch).
+pub trait Unacknowledged: facet::Facet<'static> {}
+
+impl Unacknowledged for MessageAck {}
+impl Unacknowledged for Connect {}
+impl Unacknowledged for Output {}
+impl Unacknowledged for Debug {}
+
+/// Check that the `line` deserializes to a `T` serializing back to the very same `line`,
+/// to validate custom message types against the wire format.
+pub fn roundtrip<'de, T: facet::Facet<'de>>(line: &'de str) -> Result<()> {
+    let output = to_string(&from_str::<T>(line)?);
+
+    if output != line {
+        return Err(Error::RoundTrip {
+            input: line.into(),
+            output,
+        });
+    }
+
+    Ok(())
+}
+
 /// **(<)** The engine sends this notification as answer to a syntactically
 /// incorrect line it received from the application.
 ///
 /// Note: _The external module SHOULD NOT send anything back to Yate
 /// in response to such a notification as it can result in an infinite loop._
+///
+/// As the original line is neither escaped nor free of colons,
+/// it is parsed with [`ErrorIn::parse`] rather than [`from_str`].
 #[derive(Debug, facet::Facet)]
 #[facet(type_tag = "Error in")]
 pub struct ErrorIn {
@@ -36,15 +91,26 @@ pub struct ErrorIn {
     pub original: String,
 }
 
+impl ErrorIn {
+    /// Parse the notification from the received `line`, keeping the original line verbatim.
+    pub fn parse(line: &str) -> Option<Self> {
+        line.strip_prefix("Error in:").map(|original| Self {
+            original: original.into(),
+        })
+    }
+}
+
 /// **(~)** Requests a **message** to be processed by the other party.
 #[derive(Debug, facet::Facet)]
 #[facet(type_tag = "%%>message")]
 pub struct Message {
-    /// An obscure unique message ID string generated by the sender.
+    /// An obscure unique message ID string generated by the sender,
+    /// empty if the sender doesn't expect an answer.
     pub id: String,
 
-    /// Time (in seconds) the message was initially created.
-    pub time: u64,
+    /// Time the message was initially created, with a precision of a second.
+    #[facet(opaque)]
+    pub time: SystemTime,
 
     /// Name of the message.
     pub name: String,
@@ -53,7 +119,26 @@ pub struct Message {
     pub retvalue: String,
 
     /// Enumeration of the key-value pairs of the message.
-    pub kv: BTreeMap<String, String>,
+    pub kv: Params,
+}
+
+impl Message {
+    /// Get the value of the parameter `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.kv.get(key).map(String::as_str)
+    }
+
+    /// Get the value of the parameter `key` as a boolean, if present and valid.
+    ///
+    /// _see [`parse_bool`] for the accepted spellings_.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(parse_bool)
+    }
+
+    /// Get the value of the parameter `key` as an integer, if present and valid.
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get(key)?.parse().ok()
+    }
 }
 
 /// **(~)** Confirmation that the **message** has been processed properly or not.
@@ -73,7 +158,7 @@ pub struct MessageAck {
     pub retvalue: String,
 
     /// Enumeration of the key-value pairs of the message.
-    pub kv: BTreeMap<String, String>,
+    pub kv: Params,
 }
 
 /// **(>)** Requests the installing of a message **handler**.
@@ -255,7 +340,7 @@ pub struct Connect {
 }
 
 /// The _role_ of the connection, for the [`Connect`] message.
-#[derive(Debug, facet::Facet)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, facet::Facet)]
 #[facet(rename_all = "lowercase")]
 #[repr(C)]
 pub enum ConnectRole {
@@ -275,6 +360,14 @@ pub enum ConnectRole {
     PlayRec,
 }
 
+impl ConnectRole {
+    /// Whether the role attaches to a channel's data, and then requires
+    /// the [`Connect::channel`] to be provided, which is otherwise omitted.
+    pub fn has_channel(self) -> bool {
+        matches!(self, Self::Play | Self::Record | Self::PlayRec)
+    }
+}
+
 /// **(>)** An _arbitrary message_ to be sent
 /// to engine's logging output.
 #[derive(Debug, facet::Facet)]
@@ -296,10 +389,15 @@ pub struct Debug {
     pub text: String,
 }
 
-/// The _log level_ of the [`struct@Debug`] message.
-#[derive(Debug, facet::Facet)]
+/// The _log level_ of the [`struct@Debug`] message,
+/// from the most severe [`DebugLevel::Fail`] (`0`) to the most verbose [`DebugLevel::All`] (`10`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, facet::Facet)]
 #[repr(C)]
 pub enum DebugLevel {
+    /// The `FAIL` log level.
+    #[facet(rename = "0")]
+    Fail,
+
     /// The `TEST` log level.
     #[facet(rename = "1")]
     Test,
@@ -341,6 +439,94 @@ pub enum DebugLevel {
     All,
 }
 
+impl DebugLevel {
+    /// The `FAIL` alias of the [`DebugLevel::Fail`] log level.
+    pub const FAIL: Self = Self::Fail;
+
+    /// The `FATAL` alias of the [`DebugLevel::Fail`] log level.
+    pub const FATAL: Self = Self::Fail;
+
+    /// The `CRIT` alias of the [`DebugLevel::Crit`] log level.
+    pub const CRIT: Self = Self::Crit;
+
+    /// The `GOON` alias of the [`DebugLevel::Crit`] log level.
+    pub const GO_ON: Self = Self::Crit;
+
+    /// The `WARN` alias of the [`DebugLevel::Warn`] log level.
+    pub const WARN: Self = Self::Warn;
+
+    /// The `INFO` alias of the [`DebugLevel::Info`] log level.
+    pub const INFO: Self = Self::Info;
+
+    /// The `ALL` alias of the [`DebugLevel::All`] log level.
+    pub const ALL: Self = Self::All;
+}
+
+impl From<DebugLevel> for u8 {
+    fn from(level: DebugLevel) -> Self {
+        match level {
+            DebugLevel::Fail => 0,
+            DebugLevel::Test => 1,
+            DebugLevel::Crit => 2,
+            DebugLevel::Conf => 3,
+            DebugLevel::Stub => 4,
+            DebugLevel::Warn => 5,
+            DebugLevel::Mild => 6,
+            DebugLevel::Note => 7,
+            DebugLevel::Call => 8,
+            DebugLevel::Info => 9,
+            DebugLevel::All => 10,
+        }
+    }
+}
+
+impl TryFrom<u8> for DebugLevel {
+    type Error = Error;
+
+    fn try_from(level: u8) -> Result<Self> {
+        Ok(match level {
+            0 => Self::Fail,
+            1 => Self::Test,
+            2 => Self::Crit,
+            3 => Self::Conf,
+            4 => Self::Stub,
+            5 => Self::Warn,
+            6 => Self::Mild,
+            7 => Self::Note,
+            8 => Self::Call,
+            9 => Self::Info,
+            10 => Self::All,
+            _ => return Err(Error::InvalidDebugLevel(level)),
+        })
+    }
+}
+
+impl From<tracing::Level> for DebugLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => Self::Crit,
+            tracing::Level::WARN => Self::Warn,
+            tracing::Level::INFO => Self::Note,
+            tracing::Level::DEBUG => Self::Info,
+            tracing::Level::TRACE => Self::All,
+        }
+    }
+}
+
+impl From<DebugLevel> for tracing::Level {
+    fn from(level: DebugLevel) -> Self {
+        match level {
+            DebugLevel::Fail | DebugLevel::Test | DebugLevel::Crit | DebugLevel::Conf => {
+                Self::ERROR
+            }
+            DebugLevel::Stub | DebugLevel::Warn | DebugLevel::Mild => Self::WARN,
+            DebugLevel::Note | DebugLevel::Call => Self::INFO,
+            DebugLevel::Info => Self::DEBUG,
+            DebugLevel::All => Self::TRACE,
+        }
+    }
+}
+
 /// **(>)** Tell the engine we'd like to stop processing messages.
 #[derive(Debug, facet::Facet)]
 #[facet(type_tag = "%%>quit")]
@@ -0,0 +1,125 @@
+use std::{borrow::Cow, convert::Infallible, fmt, str::FromStr};
+
+macro_rules! names {
+    ($($(#[$meta:meta])* $variant:ident => $name:literal,)*) => {
+        /// The name of a [`Message`](super::Message), with the well-known Yate messages
+        /// enumerated and any other name kept as-is in [`MessageName::Other`].
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum MessageName {
+            $(
+                $(#[$meta])*
+                #[doc = concat!("The `", $name, "` message.")]
+                $variant,
+            )*
+
+            /// Any other message name.
+            Other(String),
+        }
+
+        impl MessageName {
+            /// The textual representation of the message name.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $name,)*
+                    Self::Other(name) => name,
+                }
+            }
+        }
+
+        impl From<String> for MessageName {
+            fn from(name: String) -> Self {
+                match name.as_str() {
+                    $($name => Self::$variant,)*
+                    _ => Self::Other(name),
+                }
+            }
+        }
+    };
+}
+
+names! {
+    EngineStart => "engine.start",
+    EngineHalt => "engine.halt",
+    EngineTimer => "engine.timer",
+    EngineStatus => "engine.status",
+    EngineCommand => "engine.command",
+    CallPreroute => "call.preroute",
+    CallRoute => "call.route",
+    CallExecute => "call.execute",
+    CallProgress => "call.progress",
+    CallRinging => "call.ringing",
+    CallAnswered => "call.answered",
+    CallDrop => "call.drop",
+    CallCdr => "call.cdr",
+    ChanStartup => "chan.startup",
+    ChanHangup => "chan.hangup",
+    ChanDisconnected => "chan.disconnected",
+    ChanNotify => "chan.notify",
+    ChanDtmf => "chan.dtmf",
+    UserAuth => "user.auth",
+    UserRegister => "user.register",
+    UserUnregister => "user.unregister",
+    ResourceNotify => "resource.notify",
+    ResourceSubscribe => "resource.subscribe",
+}
+
+impl FromStr for MessageName {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(name))
+    }
+}
+
+impl From<&str> for MessageName {
+    fn from(name: &str) -> Self {
+        name.to_owned().into()
+    }
+}
+
+impl From<&String> for MessageName {
+    fn from(name: &String) -> Self {
+        name.clone().into()
+    }
+}
+
+// Mirror the `From` implementations of `String`, so that any `impl Into<String>` name still converts
+
+impl From<&mut str> for MessageName {
+    fn from(name: &mut str) -> Self {
+        name.to_owned().into()
+    }
+}
+
+impl From<Box<str>> for MessageName {
+    fn from(name: Box<str>) -> Self {
+        String::from(name).into()
+    }
+}
+
+impl From<Cow<'_, str>> for MessageName {
+    fn from(name: Cow<'_, str>) -> Self {
+        name.into_owned().into()
+    }
+}
+
+impl From<char> for MessageName {
+    fn from(name: char) -> Self {
+        String::from(name).into()
+    }
+}
+
+impl From<MessageName> for String {
+    fn from(name: MessageName) -> Self {
+        match name {
+            MessageName::Other(name) => name,
+            name => name.as_str().to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for MessageName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,185 @@
+//! The key-value pairs of the messages, see [`Params`].
+
+use std::{collections::BTreeMap, fmt, ops::Index};
+
+#[cfg(feature = "indexmap")]
+type Map = indexmap::IndexMap<String, String>;
+
+#[cfg(not(feature = "indexmap"))]
+type Map = BTreeMap<String, String>;
+
+/// The key-value pairs of a [`Message`](super::Message), iterated in key order,
+/// unless the `indexmap` feature is enabled to keep them in wire order.
+///
+/// The API is the same whether or not the feature is enabled, only the iteration order differs.
+#[derive(Clone, Default, PartialEq, Eq, facet::Facet)]
+#[facet(transparent)]
+pub struct Params(Map);
+
+impl Params {
+    /// Create an empty set of parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the value of the parameter `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.0.get(key)
+    }
+
+    /// Whether the parameter `key` is present.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Set the parameter `key` to `value`, returning it's previous value if it was present.
+    ///
+    /// A parameter that was already present keeps it's position in the iteration order.
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        self.0.insert(key, value)
+    }
+
+    /// Remove the parameter `key`, returning it's value if it was present.
+    ///
+    /// The order of the remaining parameters is preserved.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        #[cfg(feature = "indexmap")]
+        return self.0.shift_remove(key);
+
+        #[cfg(not(feature = "indexmap"))]
+        self.0.remove(key)
+    }
+
+    /// Only keep the parameters for which `keep` returns `true`.
+    pub fn retain(&mut self, keep: impl FnMut(&String, &mut String) -> bool) {
+        self.0.retain(keep);
+    }
+
+    /// Iterate over the parameters.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+
+    /// Iterate over the keys of the parameters.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+
+    /// Iterate over the values of the parameters.
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.0.values()
+    }
+}
+
+impl fmt::Debug for Params {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Index<&str> for Params {
+    type Output = String;
+
+    /// Get the value of the parameter `key`.
+    ///
+    /// # Panics
+    ///
+    /// If the parameter `key` is not present.
+    fn index(&self, key: &str) -> &String {
+        &self.0[key]
+    }
+}
+
+impl FromIterator<(String, String)> for Params {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl Extend<(String, String)> for Params {
+    fn extend<T: IntoIterator<Item = (String, String)>>(&mut self, iter: T) {
+        self.0.extend(iter);
+    }
+}
+
+impl<const N: usize> From<[(String, String); N]> for Params {
+    fn from(pairs: [(String, String); N]) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+impl From<BTreeMap<String, String>> for Params {
+    fn from(map: BTreeMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<Params> for BTreeMap<String, String> {
+    fn from(params: Params) -> Self {
+        params.into_iter().collect()
+    }
+}
+
+/// An iterator over the parameters of [`Params`], see [`Params::iter`].
+pub struct Iter<'p>(
+    #[cfg(feature = "indexmap")] indexmap::map::Iter<'p, String, String>,
+    #[cfg(not(feature = "indexmap"))] std::collections::btree_map::Iter<'p, String, String>,
+);
+
+impl<'p> Iterator for Iter<'p> {
+    type Item = (&'p String, &'p String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'p> IntoIterator for &'p Params {
+    type Item = (&'p String, &'p String);
+    type IntoIter = Iter<'p>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An owning iterator over the parameters of [`Params`].
+pub struct IntoIter(
+    #[cfg(feature = "indexmap")] indexmap::map::IntoIter<String, String>,
+    #[cfg(not(feature = "indexmap"))] std::collections::btree_map::IntoIter<String, String>,
+);
+
+impl Iterator for IntoIter {
+    type Item = (String, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl IntoIterator for Params {
+    type Item = (String, String);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.0.into_iter())
+    }
+}
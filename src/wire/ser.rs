@@ -1,52 +1,139 @@
-use facet::{Def, Facet, HasFields, Peek, PeekEnum, PeekMap, PeekOption};
+use std::time::{Duration, SystemTime};
+
+use facet::{Def, Facet, HasFields, Opaque, Peek, PeekEnum, PeekList, PeekMap, PeekOption};
+
+use super::{Error, Result};
 
 #[derive(Default)]
 struct Serializer {
-    parts: Vec<String>,
+    out: String,
+    sep: Option<char>,
+    untagged: bool,
+
+    /// Whether a map key is being serialized.
+    key: bool,
 }
 
 impl Serializer {
+    /// Begin a new part in the output, prefixed by the pending separator, if any.
+    fn part(&mut self) -> &mut String {
+        if let Some(sep) = self.sep.replace(':') {
+            self.out.push(sep);
+        }
+
+        &mut self.out
+    }
+
+    /// Encode the `value` in a new part, escaping `=` in map keys.
+    fn encode(&mut self, value: &str) {
+        if self.key {
+            super::upcode::encode_key_into(value, self.part());
+        } else {
+            super::upcode::encode_into(value, self.part());
+        }
+    }
+
     fn serialize_tag(&mut self, tag: &str) {
-        self.parts.push(tag.to_string());
+        self.part().push_str(tag);
+    }
+
+    fn serialize_scalar(&mut self, peek: Peek<'_, '_>) {
+        if let Ok(duration) = peek.get::<Duration>() {
+            self.encode(&duration.as_millis().to_string());
+        } else if let Ok(Opaque(time)) = peek.get::<Opaque<SystemTime>>() {
+            // Clamp times before the epoch to the epoch itself, as the wire can't represent them
+            let secs = time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+
+            self.encode(&secs.to_string());
+        } else {
+            self.encode(&peek.to_string());
+        }
     }
 
-    fn serialize_scalar(&mut self, peek: Peek<'_, 'static>) {
-        self.parts
-            .push(super::upcode::encode(&peek.to_string()).into_owned());
+    fn serialize_borrowed(&mut self, peek: Peek<'_, '_>) -> Result<()> {
+        let value = peek
+            .as_str()
+            .ok_or_else(|| Error::UnsupportedType(peek.shape().to_string()))?;
+        self.encode(value);
+
+        Ok(())
     }
 
-    fn serialize_option(&mut self, peek: PeekOption<'_, 'static>, has_default: bool) {
+    fn serialize_option(&mut self, peek: PeekOption<'_, '_>, has_default: bool) -> Result<()> {
         match peek.value() {
             None if has_default => (),
-            None => self.parts.push(Default::default()),
-            Some(peek) => self.serialize_value(peek, has_default),
+            None => {
+                self.part();
+            }
+            Some(peek) => self.serialize_value(peek, has_default)?,
         }
+
+        Ok(())
     }
 
-    fn serialize_map(&mut self, peek: PeekMap<'_, 'static>) {
+    fn serialize_map(&mut self, peek: PeekMap<'_, '_>) -> Result<()> {
+        // Reserve the unescaped length of the pairs and their separators up front,
+        // rather than growing the output pair by pair for large maps
+        let len = peek
+            .iter()
+            .map(|(k, v)| {
+                let len = |peek: Peek<'_, '_>| peek.as_str().map_or(0, str::len);
+
+                len(k) + len(v) + 2
+            })
+            .sum();
+        self.out.reserve(len);
+
         for (k, v) in peek.iter() {
-            self.serialize_value(k, false);
-            let k = self.parts.pop().expect("key not serialized");
+            let (mark, sep) = (self.out.len(), self.sep);
+
+            self.key = true;
+            self.serialize_value(k, false)?;
+            self.key = false;
+
+            // Glue the value to it's key in the same part
+            self.sep = Some('=');
+            self.serialize_value(v, false)?;
+
+            // Skip the key altogether if the value produced no part to glue it to
+            if self.sep == Some('=') {
+                self.out.truncate(mark);
+                self.sep = sep;
+            }
+        }
 
-            self.serialize_value(v, false);
-            let v = self.parts.pop().expect("value not serialized");
+        Ok(())
+    }
 
-            self.parts.push(format!("{k}={v}"));
+    fn serialize_list(&mut self, peek: PeekList<'_, '_>) -> Result<()> {
+        for item in peek.iter() {
+            self.serialize_value(item, false)?;
         }
+
+        Ok(())
     }
 
-    fn serialize_enum(&mut self, peek: PeekEnum<'_, 'static>) {
-        self.parts.push(
-            peek.active_variant()
-                .expect("no active variant")
-                .effective_name()
-                .into(),
-        )
+    fn serialize_enum(&mut self, peek: PeekEnum<'_, '_>) -> Result<()> {
+        let name = peek
+            .active_variant()
+            .map_err(|_| Error::UnsupportedType(peek.shape().to_string()))?
+            .effective_name();
+        self.part().push_str(name);
+
+        Ok(())
     }
 
-    fn serialize_value(&mut self, peek: Peek<'_, 'static>, has_default: bool) {
+    fn serialize_value(&mut self, peek: Peek<'_, '_>, has_default: bool) -> Result<()> {
         if let Some(tag) = peek.shape().type_tag {
-            self.serialize_tag(tag);
+            if self.untagged {
+                // Only skip the top-level tag, once
+                self.untagged = false;
+            } else {
+                self.serialize_tag(tag);
+            }
         };
 
         if let Ok(peek) = peek.into_struct() {
@@ -58,38 +145,74 @@ impl Serializer {
                             .field
                             .map(|field| field.has_default())
                             .unwrap_or_default(),
-                );
+                )?;
             }
         } else if let Ok(peek) = peek.into_enum() {
-            self.serialize_enum(peek);
+            self.serialize_enum(peek)?;
         } else if let Ok(peek) = peek.into_option() {
-            self.serialize_option(peek, has_default);
+            self.serialize_option(peek, has_default)?;
         } else if let Ok(peek) = peek.into_map() {
-            self.serialize_map(peek);
+            self.serialize_map(peek)?;
+        } else if let Ok(peek) = peek.into_list() {
+            self.serialize_list(peek)?;
         } else {
             match peek.shape().def {
                 Def::Scalar => self.serialize_scalar(peek),
+                _ if peek.shape().is_type::<Opaque<SystemTime>>() => self.serialize_scalar(peek),
+                _ if peek.shape().is_type::<&str>() => self.serialize_borrowed(peek)?,
 
-                _ => panic!(
-                    "unable to serialize type `{}`, still holding {:?}",
-                    peek.shape(),
-                    self.parts
-                ),
+                _ => return Err(Error::UnsupportedType(peek.shape().to_string())),
             }
         }
+
+        Ok(())
     }
 
-    pub fn serialize(mut self, peek: Peek<'_, 'static>) -> String {
-        self.serialize_value(peek, false);
+    pub fn serialize(mut self, peek: Peek<'_, '_>) -> Result<String> {
+        self.serialize_value(peek, false)?;
 
-        self.parts.join(":")
+        Ok(self.out)
     }
 }
 
 /// Serialize an instance of `T` to it's textual representation.
-pub fn to_string<T: Facet<'static>>(value: &T) -> String {
+///
+/// # Panics
+///
+/// If `T` holds a type that can't be represented in the wire format,
+/// which never happens for the messages of this crate, see [`try_to_string`].
+pub fn to_string<'f, T: Facet<'f>>(value: &T) -> String {
+    try_to_string(value).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Serialize an instance of `T` to it's textual representation,
+/// failing with [`Error::UnsupportedType`] if it holds a type that can't be represented in the wire format.
+pub fn try_to_string<'f, T: Facet<'f>>(value: &T) -> Result<String> {
     let peek = Peek::new(value);
     let ser = Serializer::default();
 
     ser.serialize(peek)
 }
+
+/// Serialize an instance of `T` to it's textual representation,
+/// omitting the leading keyword tag, to only keep the fields.
+///
+/// # Panics
+///
+/// If `T` holds a type that can't be represented in the wire format,
+/// which never happens for the messages of this crate, see [`try_to_string_fields`].
+pub fn to_string_fields<'f, T: Facet<'f>>(value: &T) -> String {
+    try_to_string_fields(value).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Serialize an instance of `T` to it's textual representation, omitting the leading keyword tag,
+/// failing with [`Error::UnsupportedType`] if it holds a type that can't be represented in the wire format.
+pub fn try_to_string_fields<'f, T: Facet<'f>>(value: &T) -> Result<String> {
+    let peek = Peek::new(value);
+    let ser = Serializer {
+        untagged: true,
+        ..Default::default()
+    };
+
+    ser.serialize(peek)
+}
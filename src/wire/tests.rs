@@ -1,10 +1,10 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, time::Duration};
 
 use facet::Facet;
 
 use super::*;
 
-fn test<T: Facet<'static> + Debug>(input: &str) {
+fn test<T: for<'de> Facet<'de> + Debug>(input: &str) -> T {
     let deserialized = from_str::<T>(input).unwrap_or_else(|err| {
         panic!(
             "can't deserialize `{}` from `{input}`: {err}",
@@ -14,17 +14,57 @@ fn test<T: Facet<'static> + Debug>(input: &str) {
     eprintln!("{deserialized:?}");
 
     let serialized = to_string(&deserialized);
+    assert_eq!(input, serialized, "for {}", T::SHAPE.type_identifier);
+
+    deserialized
+}
+
+fn test_fields<T: for<'de> Facet<'de> + Debug>(input: &str) {
+    let deserialized = from_str_fields::<T>(input).unwrap_or_else(|err| {
+        panic!(
+            "can't deserialize `{}` fields from `{input}`: {err}",
+            T::SHAPE.type_identifier
+        )
+    });
+    eprintln!("{deserialized:?}");
+
+    let serialized = to_string_fields(&deserialized);
     assert_eq!(input, serialized, "for {}", T::SHAPE.type_identifier)
 }
 
 #[test]
 fn errors() {
     std::panic::catch_unwind(|| test::<ConnectRole>("other")).unwrap_err();
+
+    assert!(matches!(from_str::<Quit>(""), Err(Error::EmptyInput)));
+
+    let err = from_str::<InstallAck>("%%<install:100:engine.timer").unwrap_err();
+    assert!(matches!(err, Error::MissingValue { field: 3 }));
+    assert_eq!(
+        err.to_string(),
+        "expected value at field 3, but input is exhausted"
+    );
+
+    let err = from_str::<InstallAck>("%%<uninstall:100:engine.timer:true").unwrap_err();
+    assert!(matches!(err, Error::MismatchedTag { field: 0 }));
+
+    let err =
+        from_str::<Message>("%%>message:yengine.1.1:1095112795:engine.timer::a=1:b").unwrap_err();
+    assert!(matches!(err, Error::MisformatedMap { field: 6, ref token } if token == "b"));
+    assert!(matches!(from_str::<Message>(" \t"), Err(Error::EmptyInput)));
 }
 
 #[test]
 fn error_in() {
     test::<ErrorIn>("Error in:this is an error");
+
+    assert_eq!(
+        ErrorIn::parse("Error in:%%>install:100:engine.timer")
+            .unwrap()
+            .original,
+        "%%>install:100:engine.timer"
+    );
+    assert!(ErrorIn::parse("%%<install:100:engine.timer:true").is_none());
 }
 
 #[test]
@@ -142,3 +182,488 @@ fn quit() {
 fn quit_ack() {
     test::<QuitAck>("%%<quit");
 }
+
+#[test]
+fn fields() {
+    test_fields::<Message>("yengine.1.1:1095112795:engine.timer::time=1095112795");
+    test_fields::<InstallAck>("100:engine.timer:true");
+    test_fields::<Connect>("play:ourstream/1");
+
+    let message = from_str::<Message>("%%>message:yengine.1.1:1095112795:engine.timer:").unwrap();
+    assert_eq!(
+        to_string_fields(&message),
+        "yengine.1.1:1095112795:engine.timer:"
+    );
+}
+
+#[test]
+fn list() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "%%>codecs")]
+    struct Codecs {
+        channel: String,
+        codecs: Vec<String>,
+    }
+
+    test::<Codecs>("%%>codecs:chan/1:alaw:mulaw:g729");
+    test::<Codecs>("%%>codecs:chan/1:alaw");
+    test::<Codecs>("%%>codecs:chan/1");
+}
+
+#[test]
+fn map_ordering() {
+    let message = Message {
+        id: "yengine.1.1".into(),
+        time: SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795),
+        name: "call.route".into(),
+        retvalue: Default::default(),
+        kv: [("called", "1234"), ("billid", "1-1"), ("caller", "5678")]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect(),
+    };
+
+    // The parameters are kept in insertion order with the `indexmap` feature
+    let kv = if cfg!(feature = "indexmap") {
+        "called=1234:billid=1-1:caller=5678"
+    } else {
+        "billid=1-1:called=1234:caller=5678"
+    };
+    assert_eq!(
+        to_string(&message),
+        format!("%%>message:yengine.1.1:1095112795:call.route::{kv}")
+    );
+}
+
+#[test]
+fn map_duplicate_keys() {
+    let line = "%%>message:yengine.1.1:1095112795:engine.timer::a=1:a=2";
+
+    let message = from_str::<Message>(line).unwrap();
+    assert_eq!(message.kv.len(), 1);
+    assert_eq!(message.kv["a"], "2");
+
+    assert!(matches!(
+        from_str_strict::<Message>(line),
+        Err(Error::DuplicateKey(key)) if key == "a"
+    ));
+
+    test::<Message>("%%>message:yengine.1.1:1095112795:engine.timer::a=1:b=2");
+}
+
+#[test]
+fn booleans() {
+    for truthy in ["true", "yes", "on", "enable", "t", "1"] {
+        let ack = from_str::<WatchAck>(&format!("%%<watch:engine.timer:{truthy}")).unwrap();
+        assert!(ack.success, "for `{truthy}`");
+        assert_eq!(to_string(&ack), "%%<watch:engine.timer:true");
+    }
+
+    for falsy in ["false", "no", "off", "disable", "f", "0"] {
+        let ack = from_str::<WatchAck>(&format!("%%<watch:engine.timer:{falsy}")).unwrap();
+        assert!(!ack.success, "for `{falsy}`");
+        assert_eq!(to_string(&ack), "%%<watch:engine.timer:false");
+    }
+
+    assert!(matches!(
+        from_str::<WatchAck>("%%<watch:engine.timer:maybe"),
+        Err(Error::InvalidValue {
+            field: 2,
+            expected: "boolean"
+        })
+    ));
+}
+
+#[test]
+fn empty_versus_absent() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "tag")]
+    struct Query {
+        name: String,
+
+        #[facet(default, attr::empty)]
+        value: Option<String>,
+    }
+
+    let absent = test::<Query>("tag:");
+    assert_eq!(absent.value, None);
+
+    let empty = test::<Query>("tag::");
+    assert_eq!(empty.value.as_deref(), Some(""));
+
+    let set = test::<Query>("tag::value");
+    assert_eq!(set.value.as_deref(), Some("value"));
+}
+
+#[test]
+fn empty_is_none() {
+    let install = from_str::<Install>("%%>install:100:call.route:").unwrap();
+    assert_eq!(install.filter, None);
+    assert_eq!(to_string(&install), "%%>install:100:call.route");
+
+    let connect = from_str::<Connect>("%%>connect:channel:").unwrap();
+    assert_eq!(connect.channel, None);
+    assert_eq!(to_string(&connect), "%%>connect:channel");
+}
+
+#[test]
+fn borrowed() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "%%>output")]
+    struct BorrowedOutput<'a> {
+        text: &'a str,
+    }
+
+    let input = String::from("%%>output:some text");
+    let output = from_str::<BorrowedOutput>(&input).unwrap();
+
+    assert_eq!(output.text, "some text");
+    assert!(std::ptr::eq(output.text, &input[10..]));
+    assert_eq!(to_string(&output), input);
+
+    assert!(matches!(
+        from_str::<BorrowedOutput>("%%>output:some%ztext"),
+        Err(Error::EscapedBorrow { field: 1 })
+    ));
+}
+
+#[test]
+fn map_empty_values() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "%%>groups")]
+    struct Groups {
+        name: String,
+        members: std::collections::BTreeMap<String, Vec<String>>,
+    }
+
+    let groups = Groups {
+        name: "name".into(),
+        members: [("a", vec![]), ("b", vec!["x".into()]), ("c", vec![])]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect(),
+    };
+
+    assert_eq!(to_string(&groups), "%%>groups:name:b=x");
+}
+
+#[test]
+fn map_field_positions() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "tag")]
+    struct Flags {
+        flags: std::collections::BTreeMap<String, bool>,
+        after: String,
+    }
+
+    assert!(matches!(
+        from_str::<Flags>("tag:a=true:b=maybe"),
+        Err(Error::InvalidValue {
+            field: 2,
+            expected: "boolean"
+        })
+    ));
+    assert!(matches!(
+        from_str::<Flags>("tag:a=true:b=false"),
+        Err(Error::MissingValue { field: 3 })
+    ));
+
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "tag")]
+    struct Groups {
+        groups: std::collections::BTreeMap<String, Vec<String>>,
+    }
+
+    let groups = from_str::<Groups>("tag:a=x:b=y").unwrap();
+    assert_eq!(groups.groups["a"], ["x"]);
+    assert_eq!(groups.groups["b"], ["y"]);
+}
+
+#[test]
+fn map_keys_with_separator() {
+    let message = test::<Message>("%%>message:id:1095112795:name::a%}b=c=d:e=f");
+
+    assert_eq!(message.kv["a=b"], "c=d");
+    assert_eq!(message.kv["e"], "f");
+}
+
+#[test]
+fn durations() {
+    #[derive(Debug, facet::Facet)]
+    #[facet(type_tag = "%%<setlocal")]
+    struct TimeoutAck {
+        name: String,
+        value: Duration,
+        success: bool,
+    }
+
+    let ack = test::<TimeoutAck>("%%<setlocal:timeout:1500:true");
+    assert_eq!(ack.value, Duration::from_millis(1500));
+
+    assert!(matches!(
+        from_str::<TimeoutAck>("%%<setlocal:timeout:1.5s:true"),
+        Err(Error::InvalidValue {
+            field: 2,
+            expected: "milliseconds"
+        })
+    ));
+}
+
+#[test]
+fn times() {
+    let message = test::<Message>("%%>message:id:1095112795:name:");
+    assert_eq!(
+        message.time,
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1095112795)
+    );
+
+    let message = test::<Message>("%%>message:id:0:name:");
+    assert_eq!(message.time, SystemTime::UNIX_EPOCH);
+
+    assert!(matches!(
+        from_str::<Message>(&format!("%%>message:id:{}:name:", u64::MAX)),
+        Err(Error::InvalidValue {
+            field: 2,
+            expected: "unix timestamp"
+        })
+    ));
+
+    let message = Message {
+        id: "id".into(),
+        time: SystemTime::UNIX_EPOCH - Duration::from_secs(1),
+        name: "name".into(),
+        retvalue: Default::default(),
+        kv: Default::default(),
+    };
+    assert_eq!(to_string(&message), "%%>message:id:0:name:");
+}
+
+#[test]
+#[cfg(feature = "indexmap")]
+fn map_wire_ordering() {
+    let message =
+        test::<Message>("%%>message:id:1095112795:call.route::called=1234:billid=1-1:caller=5678");
+
+    assert_eq!(
+        message.kv.keys().collect::<Vec<_>>(),
+        ["called", "billid", "caller"]
+    );
+}
+
+#[test]
+fn message_accessors() {
+    let message = from_str::<Message>(
+        "%%>message:id:1095112795:call.route::caller=1234:answered=yes:duration=-12:early=maybe",
+    )
+    .unwrap();
+
+    assert_eq!(message.get("caller"), Some("1234"));
+    assert_eq!(message.get("called"), None);
+
+    assert_eq!(message.get_bool("answered"), Some(true));
+    assert_eq!(message.get_bool("early"), None);
+    assert_eq!(message.get_bool("missing"), None);
+
+    assert_eq!(message.get_int("duration"), Some(-12));
+    assert_eq!(message.get_int("caller"), Some(1234));
+    assert_eq!(message.get_int("answered"), None);
+    assert_eq!(message.get_int("missing"), None);
+}
+
+#[test]
+fn message_names() {
+    for name in [
+        "engine.timer",
+        "call.route",
+        "chan.hangup",
+        "resource.notify",
+    ] {
+        let parsed = name.parse::<MessageName>().unwrap();
+
+        assert!(!matches!(parsed, MessageName::Other(_)), "for `{name}`");
+        assert_eq!(parsed.as_str(), name);
+        assert_eq!(String::from(parsed), name);
+    }
+
+    assert_eq!(MessageName::from("call.route"), MessageName::CallRoute);
+    assert_eq!(
+        MessageName::from(Box::<str>::from("call.route")),
+        MessageName::CallRoute
+    );
+    assert_eq!(
+        MessageName::from(std::borrow::Cow::Borrowed("call.route")),
+        MessageName::CallRoute
+    );
+    assert_eq!(MessageName::from('x'), MessageName::Other("x".into()));
+    assert_eq!(MessageName::EngineTimer.to_string(), "engine.timer");
+
+    let parsed = "my.custom.message".parse::<MessageName>().unwrap();
+    assert_eq!(parsed, MessageName::Other("my.custom.message".into()));
+    assert_eq!(String::from(parsed), "my.custom.message");
+}
+
+#[test]
+fn roundtrip_custom_type() {
+    #[derive(Debug, Facet)]
+    #[facet(type_tag = "%%>custom")]
+    struct Custom {
+        count: u64,
+        enabled: bool,
+    }
+
+    roundtrip::<Custom>("%%>custom:7:true").unwrap();
+    roundtrip::<InstallAck>("%%<install:100:call.route:true").unwrap();
+
+    assert!(matches!(
+        roundtrip::<Custom>("%%>custom:007:yes"),
+        Err(Error::RoundTrip { input, output })
+            if input == "%%>custom:007:yes" && output == "%%>custom:7:true"
+    ));
+    assert!(matches!(
+        roundtrip::<Custom>("%%>custom:seven:true"),
+        Err(Error::Reflect(_))
+    ));
+}
+
+#[test]
+fn from_str_any() {
+    assert!(matches!(
+        try_from_str_any("%%>message:234479208:1095112795:call.route::caller=bob"),
+        Ok(AnyMessage::Message(Message { name, .. })) if name == "call.route"
+    ));
+    assert!(matches!(
+        try_from_str_any("%%<install:100:call.route:true"),
+        Ok(AnyMessage::InstallAck(InstallAck { success: true, .. }))
+    ));
+    assert!(matches!(
+        try_from_str_any("%%<quit"),
+        Ok(AnyMessage::QuitAck(QuitAck))
+    ));
+    assert!(matches!(
+        try_from_str_any("Error in:%%>bogus"),
+        Ok(AnyMessage::ErrorIn(ErrorIn { original })) if original == "%%>bogus"
+    ));
+
+    assert!(matches!(try_from_str_any(""), Err(Error::EmptyInput)));
+    assert!(matches!(
+        try_from_str_any("%%>bogus:line"),
+        Err(Error::MismatchedTag { field: 0 })
+    ));
+    assert!(try_from_str_any("%%<install:high:call.route:true").is_err());
+    assert!(try_from_str_any("%%>debug:42:text").is_err());
+}
+
+#[test]
+fn unsupported_type() {
+    #[derive(Debug, Facet)]
+    #[facet(type_tag = "%%>pair")]
+    struct Pair {
+        values: [u8; 2],
+    }
+
+    assert!(matches!(
+        from_str::<Pair>("%%>pair:1:2"),
+        Err(Error::UnsupportedType(_))
+    ));
+    assert!(matches!(
+        try_to_string(&Pair { values: [1, 2] }),
+        Err(Error::UnsupportedType(_))
+    ));
+    assert!(matches!(
+        try_to_string_fields(&Pair { values: [1, 2] }),
+        Err(Error::UnsupportedType(_))
+    ));
+}
+
+#[test]
+fn debug_levels() {
+    for level in 0..=10 {
+        let debug = super::Debug {
+            level: DebugLevel::try_from(level).unwrap(),
+            text: "text".into(),
+        };
+        assert_eq!(u8::from(debug.level), level);
+
+        let line = to_string(&debug);
+        assert_eq!(line, format!("%%>debug:{level}:text"));
+        assert_eq!(from_str::<super::Debug>(&line).unwrap().level, debug.level);
+    }
+
+    assert!(matches!(
+        DebugLevel::try_from(11),
+        Err(Error::InvalidDebugLevel(11))
+    ));
+
+    assert_eq!(DebugLevel::FAIL, DebugLevel::Fail);
+    assert_eq!(u8::from(DebugLevel::FAIL), 0);
+    assert_eq!(DebugLevel::FATAL, DebugLevel::Fail);
+    assert_eq!(DebugLevel::CRIT, DebugLevel::Crit);
+    assert_eq!(DebugLevel::GO_ON, DebugLevel::Crit);
+    assert_eq!(DebugLevel::WARN, DebugLevel::Warn);
+    assert_eq!(DebugLevel::INFO, DebugLevel::Info);
+    assert_eq!(DebugLevel::ALL, DebugLevel::All);
+    assert!(DebugLevel::FAIL < DebugLevel::Test);
+    assert!(DebugLevel::Crit < DebugLevel::Info);
+
+    for level in [
+        tracing::Level::ERROR,
+        tracing::Level::WARN,
+        tracing::Level::INFO,
+        tracing::Level::DEBUG,
+        tracing::Level::TRACE,
+    ] {
+        assert_eq!(tracing::Level::from(DebugLevel::from(level)), level);
+    }
+}
+
+#[test]
+fn trailing_fields() {
+    let line = "%%<install:100:engine.timer:true:futurefield";
+
+    let ack = from_str::<InstallAck>(line).unwrap();
+    assert_eq!(
+        (ack.priority, ack.name.as_str(), ack.success),
+        (100, "engine.timer", true)
+    );
+
+    assert!(matches!(
+        from_str_strict::<InstallAck>(line),
+        Err(Error::TrailingFields { field: 4 })
+    ));
+    assert!(from_str_strict::<InstallAck>("%%<install:100:engine.timer:true").is_ok());
+
+    assert!(matches!(
+        roundtrip::<InstallAck>(line),
+        Err(Error::RoundTrip { output, .. }) if output == "%%<install:100:engine.timer:true"
+    ));
+}
+
+#[test]
+fn large_maps() {
+    let kv = (0..100)
+        .map(|n| format!(":key{n:03}=value%z{n}"))
+        .collect::<String>();
+
+    test::<Message>(&format!("%%>message:234479288:1095112796:call.route:{kv}"));
+}
+
+#[test]
+fn many_map_pairs() {
+    let kv = (0..50)
+        .map(|n| format!(":key{n:02}=value%z{n}"))
+        .collect::<String>();
+    let message = test::<Message>(&format!("%%>message:234479288:1095112796:call.route:{kv}"));
+
+    assert_eq!(message.kv.len(), 50);
+    for n in 0..50 {
+        assert_eq!(
+            message.get(&format!("key{n:02}")),
+            Some(format!("value:{n}").as_str())
+        );
+    }
+
+    assert!(matches!(
+        from_str::<Message>("%%>message:234479288:1095112796:call.route::a=1:b:c=3"),
+        Err(Error::MisformatedMap { field: 6, ref token }) if token == "b"
+    ));
+}
@@ -25,72 +25,182 @@ use thiserror::Error;
 
 /// An error that may occur while decoding `%`-encoded values.
 #[derive(Debug, Error)]
-#[error("invalid upcode `{0}`, not in 64..=127 range")]
-pub struct DecodeError(char);
-
-fn updecode(ch: char) -> Result<char, DecodeError> {
-    if ch == '%' {
-        Ok(ch)
-    } else {
-        match u8::try_from(ch) {
-            Ok(code @ 64..=127) => Ok(char::from(code - 64)),
-            _ => Err(DecodeError(ch)),
-        }
+pub enum DecodeError {
+    /// The `%` was followed by an invalid upcode.
+    #[error("invalid upcode `{0}`, not in 64..=127 range")]
+    InvalidUpcode(char),
+
+    /// The value ended with a `%` not followed by any upcode.
+    #[error("dangling `%` at the end of the value")]
+    Dangling,
+}
+
+fn updecode(code: u8) -> Result<u8, DecodeError> {
+    match code {
+        b'%' => Ok(code),
+        64..=127 => Ok(code - 64),
+        _ => Err(DecodeError::InvalidUpcode(char::from(code))),
     }
 }
 
-/// Decode a `%`-encoded value to a string.
-pub fn decode(value: &str) -> Result<Cow<'_, str>, DecodeError> {
-    if !value.contains('%') {
-        return Ok(value.into());
+/// An incremental decoder of `%`-encoded values,
+/// allowing to decode a value split across multiple chunks.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    pending: bool,
+}
+
+impl Decoder {
+    /// Decode the `chunk` and append the result to `out`,
+    /// carrying a dangling `%` over to the next chunk.
+    pub fn push(&mut self, chunk: &str, out: &mut String) -> Result<(), DecodeError> {
+        for ch in chunk.chars() {
+            if self.pending {
+                self.pending = false;
+
+                let code = u8::try_from(ch).map_err(|_| DecodeError::InvalidUpcode(ch))?;
+                out.push(char::from(updecode(code)?));
+            } else if ch == '%' {
+                self.pending = true;
+            } else {
+                out.push(ch);
+            }
+        }
+
+        Ok(())
     }
 
-    let mut decoded = String::with_capacity(value.len());
-    let mut decoding = false;
-    for ch in value.chars() {
-        if decoding {
-            decoding = false;
-            decoded.push(updecode(ch)?);
-        } else if ch == '%' {
-            decoding = true;
+    /// Finish the decoding, erroring if the value ended with a dangling `%`.
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.pending {
+            Err(DecodeError::Dangling)
         } else {
-            decoded.push(ch);
+            Ok(())
         }
     }
+}
+
+/// Decode a `%`-encoded value to bytes.
+pub fn decode_bytes(value: &[u8]) -> Result<Cow<'_, [u8]>, DecodeError> {
+    if memchr::memchr(b'%', value).is_none() {
+        return Ok(value.into());
+    }
+
+    // Copy the runs between the `%` in bulk
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(idx) = memchr::memchr(b'%', rest) {
+        decoded.extend_from_slice(&rest[..idx]);
+
+        let code = *rest.get(idx + 1).ok_or(DecodeError::Dangling)?;
+        decoded.push(updecode(code)?);
+
+        rest = &rest[idx + 2..];
+    }
+    decoded.extend_from_slice(rest);
 
     Ok(decoded.into())
 }
 
-fn upencode(ch: char) -> char {
-    if ch == '%' {
-        ch
-    } else {
-        char::from(ch as u8 + 64)
-    }
+/// Decode a `%`-encoded value to a string.
+pub fn decode(value: &str) -> Result<Cow<'_, str>, DecodeError> {
+    Ok(match decode_bytes(value.as_bytes())? {
+        Cow::Borrowed(_) => value.into(),
+        Cow::Owned(decoded) => String::from_utf8(decoded)
+            .expect("decoding only substitutes ASCII bytes")
+            .into(),
+    })
 }
 
-/// Encode a string to a `%`-encoded value.
-pub fn encode(value: &str) -> Cow<'_, str> {
-    let pred = |ch: &char| ch.is_ascii_control() || matches!(ch, '%' | ':');
-    let encodable = value.chars().filter(pred).count();
+fn upencode(byte: u8) -> u8 {
+    if byte == b'%' { byte } else { byte + 64 }
+}
+
+/// Iterate over the positions of the bytes of `value` to encode, the control characters `<32`,
+/// `%`, `:` and the `extra` byte, as `DEL` (`127`) would be out of the upcode range.
+fn encodables(value: &[u8], extra: u8) -> impl Iterator<Item = usize> + '_ {
+    // Quickly find the special bytes, and only look for control characters between them
+    let mut specials = memchr::memchr3_iter(b'%', b':', extra, value);
+    let mut special = specials.next();
+    let mut from = 0;
+
+    std::iter::from_fn(move || {
+        let until = special.unwrap_or(value.len());
+        let found = match value[from..until].iter().position(|&byte| byte < 32) {
+            Some(idx) => from + idx,
+            None => std::mem::replace(&mut special, specials.next())?,
+        };
+
+        from = found + 1;
+        Some(found)
+    })
+}
+
+/// The extra byte to encode in values, none besides the default ones.
+const VALUE: u8 = b'%';
+
+/// The extra byte to encode in map keys, the `=` key-value separator.
+const KEY: u8 = b'=';
 
-    if encodable == 0 {
+/// Encode bytes to a `%`-encoded value.
+pub fn encode_bytes(value: &[u8]) -> Cow<'_, [u8]> {
+    let mut encodables = encodables(value, VALUE).peekable();
+    if encodables.peek().is_none() {
         return value.into();
     }
 
-    let mut encoded = String::with_capacity(value.len() + encodable);
-    for ch in value.chars() {
-        if pred(&ch) {
-            encoded.push('%');
-            encoded.push(upencode(ch))
-        } else {
-            encoded.push(ch);
-        }
+    // Copy the unescaped runs in bulk, and grow the buffer as needed from there on
+    let mut encoded = Vec::with_capacity(value.len() + 2);
+
+    let mut from = 0;
+    for idx in encodables {
+        encoded.extend_from_slice(&value[from..idx]);
+        encoded.extend_from_slice(&[b'%', upencode(value[idx])]);
+
+        from = idx + 1;
     }
+    encoded.extend_from_slice(&value[from..]);
 
     encoded.into()
 }
 
+/// Encode a string to a `%`-encoded value.
+pub fn encode(value: &str) -> Cow<'_, str> {
+    match encode_bytes(value.as_bytes()) {
+        Cow::Borrowed(_) => value.into(),
+        Cow::Owned(encoded) => String::from_utf8(encoded)
+            .expect("encoding only substitutes ASCII bytes")
+            .into(),
+    }
+}
+
+fn encode_with(value: &str, extra: u8, out: &mut String) {
+    out.reserve(value.len());
+
+    let mut from = 0;
+    for idx in encodables(value.as_bytes(), extra) {
+        // The encodable byte is ASCII, so `idx` is on a char boundary
+        out.push_str(&value[from..idx]);
+        out.push('%');
+        out.push(char::from(upencode(value.as_bytes()[idx])));
+
+        from = idx + 1;
+    }
+
+    out.push_str(&value[from..]);
+}
+
+/// Encode a string to a `%`-encoded value, appending it to `out`.
+pub fn encode_into(value: &str, out: &mut String) {
+    encode_with(value, VALUE, out);
+}
+
+/// Encode a map key to a `%`-encoded value, appending it to `out`,
+/// also escaping any `=` which would otherwise be taken as the key-value separator.
+pub fn encode_key_into(value: &str, out: &mut String) {
+    encode_with(value, KEY, out);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,6 +258,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_encodes_control_runs_around_specials() {
+        assert_eq!(encode("\n\n:\t\t%\r"), "%J%J%z%I%I%%%M");
+
+        let mut out = String::new();
+        encode_key_into("\n=\n:=", &mut out);
+        assert_eq!(out, "%J%}%J%z%}");
+    }
+
+    #[test]
+    fn it_encodes_into() {
+        let mut out = String::from("prefix:");
+        encode_into("a:null\0separated%string", &mut out);
+        encode_into("-suffix", &mut out);
+
+        assert_eq!(out, "prefix:a%znull%@separated%%string-suffix");
+    }
+
+    #[test]
+    fn it_encodes_keys_into() {
+        let mut out = String::new();
+        encode_key_into("a=b:c=", &mut out);
+
+        assert_eq!(out, "a%}b%zc%}");
+        assert_eq!(decode(&out).unwrap(), "a=b:c=");
+    }
+
     #[test]
     fn its_consistent() {
         assert_eq!(encode(&decode("engine.timer").unwrap()), "engine.timer");
@@ -160,6 +297,19 @@ mod tests {
         assert_eq!(decode(&encode("\0%:\\?")).unwrap(), "\0%:\\?");
     }
 
+    #[test]
+    fn its_consistent_for_all_bytes() {
+        for byte in 0..=u8::MAX {
+            let value = char::from(byte).to_string();
+
+            assert_eq!(
+                decode(&encode(&value)).unwrap(),
+                value,
+                "for byte {byte:#04x}"
+            );
+        }
+    }
+
     #[test]
     fn it_rejects_bad_upcodes() {
         assert!(decode("%\n").is_err());
@@ -169,4 +319,61 @@ mod tests {
         assert!(decode("%™").is_err());
         assert!(decode("% ").is_err());
     }
+
+    #[test]
+    fn zc_encoding_large_values() {
+        let value = "engine.timer".repeat(64 * 1024 / 12);
+
+        assert!(matches!(encode(&value), Cow::Borrowed(_)));
+        assert!(matches!(decode(&value), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn it_encodes_large_values() {
+        let value = "engine.timer:%\n".repeat(64 * 1024 / 15);
+        let encoded = encode(&value);
+
+        assert_eq!(encoded, "engine.timer%z%%%J".repeat(64 * 1024 / 15));
+        assert_eq!(decode(&encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn its_consistent_for_bytes() {
+        let value = b"\xff\x00%:\xc3\x28\x80";
+
+        assert_eq!(encode_bytes(value).as_ref(), b"\xff%@%%%z\xc3\x28\x80");
+        assert_eq!(decode_bytes(&encode_bytes(value)).unwrap().as_ref(), value);
+
+        for byte in 0..=u8::MAX {
+            assert_eq!(
+                decode_bytes(&encode_bytes(&[byte])).unwrap().as_ref(),
+                [byte],
+                "for byte {byte:#04x}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_decodes_incrementally() {
+        let value = "a%@b";
+
+        for idx in 0..=value.len() {
+            let (head, tail) = value.split_at(idx);
+
+            let mut decoded = String::new();
+            let mut decoder = Decoder::default();
+            decoder.push(head, &mut decoded).unwrap();
+            decoder.push(tail, &mut decoded).unwrap();
+            decoder.finish().unwrap();
+
+            assert_eq!(decoded, decode(value).unwrap(), "split at {idx}");
+        }
+    }
+
+    #[test]
+    fn it_rejects_dangling_percent() {
+        assert!(matches!(decode("%"), Err(DecodeError::Dangling)));
+        assert!(matches!(decode("abc%"), Err(DecodeError::Dangling)));
+        assert!(matches!(decode("a%%b%"), Err(DecodeError::Dangling)));
+    }
 }